@@ -85,12 +85,16 @@ impl PlayerCamera {
         }
     }
 
-    pub fn calc_matrix(&self) -> cgmath::Matrix4<f32> {
+    pub fn forward(&self) -> cgmath::Vector3<f32> {
         let (sin_pitch, cos_pitch) = self.pitch.0.sin_cos();
         let (sin_yaw, cos_yaw) = self.yaw.0.sin_cos();
+        cgmath::Vector3::new(cos_pitch * cos_yaw, sin_pitch, cos_pitch * sin_yaw).normalize()
+    }
+
+    pub fn calc_matrix(&self) -> cgmath::Matrix4<f32> {
         let matrix = cgmath::Matrix4::look_to_rh(
             self.position,
-            cgmath::Vector3::new(cos_pitch * cos_yaw, sin_pitch, cos_pitch * sin_yaw).normalize(),
+            self.forward(),
             cgmath::Vector3::unit_y(),
         );
         matrix
@@ -0,0 +1,92 @@
+use std::fs;
+use anyhow::{Context, Result};
+use serde::Deserialize;
+
+use super::world::{chunk::{ChunkDims, DEFAULT_CHUNK_SIZE}, storage::StorageKind, generator::TerrainKind, height_image::HeightImageConfig, terrain::DEFAULT_SEA_LEVEL, mesh_builder::MesherKind};
+
+const CONFIG_FILE: &str = "config.ron";
+const DEFAULT_SUPERFLAT_LAYERS: &str = "stone,3*dirt,grass";
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct Config {
+    pub seed: u64,
+    pub chunk_size: usize,
+    // vertical chunk edge, same as chunk_size when not set
+    pub chunk_height: Option<usize>,
+    pub storage: StorageKind,
+    pub terrain: TerrainKind,
+    // terrain below this height is flooded with water
+    pub sea_level: i32,
+    // layers of the superflat generator, bottom to top
+    pub superflat_layers: String,
+    // heightmap read by the image generator
+    pub image: HeightImageConfig,
+    pub mesher: MesherKind,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            seed: 0,
+            chunk_size: DEFAULT_CHUNK_SIZE,
+            chunk_height: None,
+            storage: StorageKind::default(),
+            terrain: TerrainKind::default(),
+            sea_level: DEFAULT_SEA_LEVEL,
+            superflat_layers: DEFAULT_SUPERFLAT_LAYERS.to_string(),
+            image: HeightImageConfig::default(),
+            mesher: MesherKind::default(),
+        }
+    }
+}
+
+impl Config {
+    // reads resources/config.ron (if present), then applies command line overrides
+    pub fn load() -> Result<Self> {
+        let mut config = Self::from_file()?;
+        config.apply_args(std::env::args().skip(1))?;
+        Ok(config)
+    }
+
+    pub fn chunk_dims(&self) -> Result<ChunkDims> {
+        ChunkDims::new(self.chunk_size, self.chunk_height.unwrap_or(self.chunk_size))
+    }
+
+    fn from_file() -> Result<Self> {
+        let mut path = std::env::current_dir()?;
+        path.push("resources");
+        path.push(CONFIG_FILE);
+
+        if !path.exists() {
+            log::info!("no config at {:?}, using defaults", path);
+            return Ok(Self::default());
+        }
+
+        let source = fs::read_to_string(&path)?;
+        ron::from_str(&source).with_context(|| format!("invalid config {:?}", path))
+    }
+
+    fn apply_args(&mut self, mut args: impl Iterator<Item = String>) -> Result<()> {
+        while let Some(arg) = args.next() {
+            let (key, value) = match arg.split_once('=') {
+                Some((key, value)) => (key.to_string(), value.to_string()),
+                None => (arg.clone(), args.next().with_context(|| format!("missing value for {arg}"))?),
+            };
+
+            match key.as_str() {
+                "--seed" => self.seed = value.parse().with_context(|| format!("invalid seed {value}"))?,
+                "--chunk-size" => self.chunk_size = value.parse().with_context(|| format!("invalid chunk size {value}"))?,
+                "--chunk-height" => self.chunk_height = Some(value.parse().with_context(|| format!("invalid chunk height {value}"))?),
+                "--storage" => self.storage = value.parse()?,
+                "--terrain" => self.terrain = value.parse()?,
+                "--layers" => self.superflat_layers = value,
+                "--image" => self.image.path = value,
+                "--mesher" => self.mesher = value.parse()?,
+                "--sea-level" => self.sea_level = value.parse().with_context(|| format!("invalid sea level {value}"))?,
+                _ => anyhow::bail!("unknown argument {key}"),
+            }
+        }
+        Ok(())
+    }
+}
@@ -3,6 +3,7 @@ pub mod window;
 pub mod state;
 pub mod render;
 pub mod texture;
+pub mod config;
 
 // modules
 pub mod camera;
@@ -35,10 +35,40 @@ impl<'a> State<'a> {
         });
 
         voxel_render_pass.set_pipeline(&self.render_pipeline);
-        let _ = self.chunks.draw(&mut voxel_render_pass, &self.camera.bind_group, &self.arena);
+        let _ = self.chunks.draw_opaque(&mut voxel_render_pass, &self.camera.bind_group, &self.arena);
 
         drop(voxel_render_pass);
 
+        // keeps the opaque depth so transparent faces behind terrain are hidden
+        let mut transparent_render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("Transparent Render Pass"),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view: &view,
+                resolve_target: None,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Load,
+                    store: wgpu::StoreOp::Store,
+                },
+            })],
+
+            depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
+                view: &self.depth_texture.view,
+                depth_ops: Some(wgpu::Operations {
+                    load: wgpu::LoadOp::Load,
+                    store: wgpu::StoreOp::Store,
+                }),
+                stencil_ops: None,
+            }),
+
+            timestamp_writes: None,
+            occlusion_query_set: None,
+        });
+
+        transparent_render_pass.set_pipeline(&self.transparent_pipeline);
+        let _ = self.chunks.draw_transparent(&mut transparent_render_pass, &self.camera.bind_group, &self.arena);
+
+        drop(transparent_render_pass);
+
         let mut text_render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
             label: Some("Text Render Pass"),
             color_attachments: &[Some(wgpu::RenderPassColorAttachment {
@@ -1,12 +1,81 @@
+use anyhow::Result;
+use generational_arena::Index;
+use wgpu::util::DeviceExt;
+
+use crate::voxgl::world::{coords::ChunkPos, registry::BlockRegistry};
+
+// size of the color table in voxel.wgsl, 16 KiB is the smallest uniform binding devices allow
+pub const MAX_BLOCK_COLORS: usize = 1024;
 
 pub struct MeshArena {
     pub buffer: generational_arena::Arena<wgpu::Buffer>,
+    pub bind_group: generational_arena::Arena<wgpu::BindGroup>,
+    // group 1 of the voxel pipeline: chunk origin and block color table
+    pub chunk_layout: wgpu::BindGroupLayout,
+    block_colors: wgpu::Buffer,
 }
 
 impl MeshArena {
-    pub fn new() -> Self {
-        Self {
-            buffer: generational_arena::Arena::with_capacity(32)
+    pub fn new(device: &wgpu::Device) -> Result<Self> {
+        let mut colors = BlockRegistry::get().color_table();
+        if colors.len() > MAX_BLOCK_COLORS {
+            anyhow::bail!("{} block colors do not fit the color table of {}", colors.len(), MAX_BLOCK_COLORS);
         }
+        colors.resize(MAX_BLOCK_COLORS, [0.0; 4]);
+
+        let block_colors = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("block_colors"),
+            contents: bytemuck::cast_slice(&colors),
+            usage: wgpu::BufferUsages::UNIFORM,
+        });
+
+        let uniform = |binding| wgpu::BindGroupLayoutEntry {
+            binding,
+            visibility: wgpu::ShaderStages::VERTEX,
+            ty: wgpu::BindingType::Buffer {
+                ty: wgpu::BufferBindingType::Uniform,
+                has_dynamic_offset: false,
+                min_binding_size: None,
+            },
+            count: None,
+        };
+        let chunk_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("chunk_layout"),
+            entries: &[uniform(0), uniform(1)],
+        });
+
+        Ok(Self {
+            buffer: generational_arena::Arena::with_capacity(32),
+            bind_group: generational_arena::Arena::with_capacity(32),
+            chunk_layout,
+            block_colors,
+        })
     }
-}
\ No newline at end of file
+
+    // uniform holding the origin of a chunk, and the bind group its meshes are drawn with
+    pub fn insert_chunk_bindings(&mut self, chunk_pos: &ChunkPos, device: &wgpu::Device) -> (Index, Index) {
+        let origin = chunk_pos.world_origin();
+        let origin_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("chunk_origin"),
+            contents: bytemuck::cast_slice(&[origin.x, origin.y, origin.z, 0.0]),
+            usage: wgpu::BufferUsages::UNIFORM,
+        });
+
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout: &self.chunk_layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: origin_buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: self.block_colors.as_entire_binding(),
+                },
+            ],
+            label: Some("chunk_bind_group"),
+        });
+
+        (self.buffer.insert(origin_buffer), self.bind_group.insert(bind_group))
+    }
+}
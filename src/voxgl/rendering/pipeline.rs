@@ -1,24 +1,45 @@
 use crate::voxgl::rendering::{utils, vertex::Vertex};
 
 pub fn create_voxel_pipeline(device: &wgpu::Device, layouts: &[&wgpu::BindGroupLayout]) -> wgpu::RenderPipeline {
+    create_pipeline(device, layouts, wgpu::BlendState::REPLACE, true, "voxel_pipeline")
+}
+
+// Blends transparent faces over what is already drawn. They are tested against the depth of the
+// opaque terrain but do not write it, so transparent faces further back still show through.
+pub fn create_transparent_pipeline(device: &wgpu::Device, layouts: &[&wgpu::BindGroupLayout]) -> wgpu::RenderPipeline {
+    create_pipeline(device, layouts, wgpu::BlendState::ALPHA_BLENDING, false, "transparent_voxel_pipeline")
+}
+
+fn create_pipeline(
+    device: &wgpu::Device, layouts: &[&wgpu::BindGroupLayout], blend: wgpu::BlendState, depth_write_enabled: bool, label: &str
+) -> wgpu::RenderPipeline {
     let shader_module = utils::create_shader_module(
-        &device,
+        device,
         include_str!("voxel.wgsl"),
         "voxel_shader",
     );
 
     let pipeline_layout = utils::create_pipeline_layout(
-        &device, "voxel_pipeline", layouts
+        device, label, layouts
     );
 
-    let render_pipeline = utils::create_render_pipeline(
-        &device,
+    utils::create_render_pipeline(
+        device,
         &pipeline_layout,
-        wgpu::TextureFormat::Rgba16Float,
-        wgpu::TextureFormat::Depth32Float,
+        wgpu::ColorTargetState {
+            format: wgpu::TextureFormat::Rgba16Float,
+            blend: Some(blend),
+            write_mask: wgpu::ColorWrites::ALL,
+        },
+        wgpu::DepthStencilState {
+            format: wgpu::TextureFormat::Depth32Float,
+            depth_write_enabled,
+            depth_compare: wgpu::CompareFunction::Less,
+            stencil: wgpu::StencilState::default(),
+            bias: wgpu::DepthBiasState::default(),
+        },
         &[Vertex::desc()],
         shader_module,
-        "voxel_pipeline",
-    );
-    render_pipeline
+        label,
+    )
 }
@@ -3,8 +3,8 @@ use wgpu::{BindGroupLayout, ShaderModule};
 pub fn create_render_pipeline(
     device: &wgpu::Device,
     layout: &wgpu::PipelineLayout,
-    color_format: wgpu::TextureFormat,
-    depth_format: wgpu::TextureFormat,
+    color_target: wgpu::ColorTargetState,
+    depth_stencil: wgpu::DepthStencilState,
     vertex_layouts: &[wgpu::VertexBufferLayout],
     shader_module: wgpu::ShaderModule,
     label: &str,
@@ -23,11 +23,7 @@ pub fn create_render_pipeline(
         fragment: Some(wgpu::FragmentState {
             module: &shader_module,
             entry_point: "fs_main",
-            targets: &[wgpu::ColorTargetState {
-                format: color_format,
-                blend: Some(wgpu::BlendState::REPLACE),
-                write_mask: wgpu::ColorWrites::ALL,
-            }.into()],
+            targets: &[Some(color_target)],
         }),
 
         primitive: wgpu::PrimitiveState {
@@ -40,13 +36,7 @@ pub fn create_render_pipeline(
             unclipped_depth: false,
         },
 
-        depth_stencil: Some(wgpu::DepthStencilState {
-            format: depth_format,
-            depth_write_enabled: true,
-            depth_compare: wgpu::CompareFunction::Less,
-            stencil: wgpu::StencilState::default(),
-            bias: wgpu::DepthBiasState::default(),
-        }),
+        depth_stencil: Some(depth_stencil),
 
         multisample: wgpu::MultisampleState {
             count: 1,
@@ -1,19 +1,36 @@
+use cgmath::Vector3;
 
+use crate::voxgl::world::quad::Face;
+
+// Chunk mesh vertex packed into 8 bytes and decoded in voxel.wgsl.
+//   position  bits 0..21   x, y, z within the chunk, 7 bits each (0..=64)
+//             bits 21..24  face, index into Face::ALL
+//             bits 24..26  ambient occlusion, 0 (open) to 3
+//             bits 26..30  light emitted by the block, 0 to 15
+//   color     entry of the block color table
 #[repr(C)]
 #[derive(Clone, Copy, Debug, bytemuck::Pod, bytemuck::Zeroable)]
 pub struct Vertex {
-    pub position: [f32; 3],
-    pub normal: [f32; 3],
-    pub color: [f32; 4],
+    pub position: u32,
+    pub color: u32,
 }
 
 impl Vertex {
-    const ATTRIBS: [wgpu::VertexAttribute; 3] = wgpu::vertex_attr_array![
-        0 => Float32x3,
-        1 => Float32x3,
-        2 => Float32x4
+    const ATTRIBS: [wgpu::VertexAttribute; 2] = wgpu::vertex_attr_array![
+        0 => Uint32,
+        1 => Uint32
     ];
 
+    pub fn new(local_pos: Vector3<u32>, face: Face, occlusion: u8, light: u8, color: u32) -> Self {
+        let position = (local_pos.x & 0x7f)
+            | (local_pos.y & 0x7f) << 7
+            | (local_pos.z & 0x7f) << 14
+            | (face as u32) << 21
+            | (occlusion.min(3) as u32) << 24
+            | (light.min(15) as u32) << 26;
+        Self { position, color }
+    }
+
     pub fn desc() -> wgpu::VertexBufferLayout<'static> {
         use std::mem;
         wgpu::VertexBufferLayout {
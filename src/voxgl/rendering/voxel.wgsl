@@ -8,10 +8,24 @@ struct CameraUniform {
 @group(0) @binding(0)
 var<uniform> camera: CameraUniform;
 
+struct ChunkUniform {
+    origin: vec4<f32>,
+};
+
+@group(1) @binding(0)
+var<uniform> chunk: ChunkUniform;
+
+// three entries per block: base, end and front color (MAX_BLOCK_COLORS in arena.rs)
+@group(1) @binding(1)
+var<uniform> block_colors: array<vec4<f32>, 1024>;
+
+// how much light a fully occluded vertex loses
+const AO_STRENGTH: f32 = 0.6;
+
+// packed as described in vertex.rs
 struct VertexInput {
-    @location(0) position: vec3<f32>,
-    @location(1) normal: vec3<f32>,
-    @location(2) color: vec4<f32>,
+    @location(0) position: u32,
+    @location(1) color: u32,
 }
 
 struct VertexOutput {
@@ -23,24 +37,28 @@ struct VertexOutput {
 @vertex
 fn vs_main(vert_in: VertexInput) -> VertexOutput {
     var out: VertexOutput;
+    // in Face order: right, left, top, bottom, front, back
     var face_shade = array<f32, 6>(
-        1.0, 0.4, // top, bottom 
-        0.4, 0.7, // right, left
-        0.4, 0.7, // front, back
+        0.4, 0.7,
+        1.0, 0.4,
+        0.4, 0.7,
     );
 
-    out.color = vert_in.color;
-    out.clip_position = camera.view_proj * vec4<f32>(vert_in.position, 1.0);
-    var face_id: i32;
+    let bits = vert_in.position;
+    let local_pos = vec3<f32>(
+        f32(bits & 0x7fu),
+        f32((bits >> 7u) & 0x7fu),
+        f32((bits >> 14u) & 0x7fu),
+    );
+    let face_id = (bits >> 21u) & 0x7u;
+    let occlusion = f32((bits >> 24u) & 0x3u) / 3.0;
+    let light = f32((bits >> 26u) & 0xfu) / 15.0;
 
-    if vert_in.normal.x > 0.0 { face_id = 2; } 
-    else if vert_in.normal.x < 0.0 { face_id = 3; }
-    else if vert_in.normal.y > 0.0 { face_id = 0; }
-    else if vert_in.normal.y < 0.0 { face_id = 1; }
-    else if vert_in.normal.z > 0.0 { face_id = 4; }
-    else if vert_in.normal.z < 0.0 { face_id = 5; }
+    out.color = block_colors[vert_in.color];
+    out.clip_position = camera.view_proj * vec4<f32>(chunk.origin.xyz + local_pos, 1.0);
 
-    out.shading = face_shade[face_id];
+    let shading = face_shade[face_id] * (1.0 - AO_STRENGTH * occlusion);
+    out.shading = mix(shading, 1.0, light);
     return out;
 }
 
@@ -48,7 +66,6 @@ fn vs_main(vert_in: VertexInput) -> VertexOutput {
 
 @fragment
 fn fs_main(vert_in: VertexOutput) -> @location(0) vec4<f32> {
-    var in = vert_in;
-    in.color *= in.shading;
-    return in.color;
+    // alpha is left alone so shading does not make translucent faces more see-through
+    return vec4<f32>(vert_in.color.rgb * vert_in.shading, vert_in.color.a);
 }
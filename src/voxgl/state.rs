@@ -11,14 +11,30 @@ use crate::voxgl::{
         player_camera::{PlayerCamera, CameraUniform},
         camera_controller::CameraController,
     },
+    config::Config,
     texture::Texture, 
-    world::chunks::Chunks,
+    world::{
+        chunks::Chunks,
+        coords::WorldPos,
+        query::{Aabb, Region},
+        quad::Face,
+        registry::BlockRegistry,
+        terrain_config::TerrainConfig,
+        voxel::{BlockState, Voxel, VoxelId},
+    },
     rendering::arena::MeshArena,
 };
 
 use super::rendering::pipeline;
 
 const CHUNK_UPDATE_RATE: i32 = 2;
+const EDIT_REACH: f32 = 8.0;
+const INSPECT_RADIUS: f32 = 32.0;
+// camera height above the ground it spawns on
+const SPAWN_EYE_HEIGHT: f32 = 2.5;
+// used when no ground is found under the spawn point
+const FALLBACK_SPAWN_HEIGHT: f32 = 20.0;
+const PLACE_BLOCK: &str = "log";
 
 pub struct State<'a> {
     pub surface: wgpu::Surface,
@@ -28,6 +44,7 @@ pub struct State<'a> {
     pub size: winit::dpi::PhysicalSize<u32>,
     pub window: Window,
     pub render_pipeline: wgpu::RenderPipeline,
+    pub transparent_pipeline: wgpu::RenderPipeline,
     pub depth_texture: Texture,
     pub arena: MeshArena,
 
@@ -36,6 +53,7 @@ pub struct State<'a> {
     pub camera_controller: CameraController,
 
     pub chunks: Chunks,
+    pub app_config: Config,
     pub sky_color: wgpu::Color,
     pub brush: wgpu_text::TextBrush<FontRef<'a>>,
 
@@ -46,7 +64,7 @@ pub struct State<'a> {
 }
 
 impl<'a> State<'a> {
-    pub async fn new(window: Window) -> Self {
+    pub async fn new(window: Window, app_config: Config, terrain_config: &TerrainConfig) -> anyhow::Result<Self> {
         let size = window.inner_size();
         let instance = wgpu::Instance::new(wgpu::InstanceDescriptor {
                 backends: wgpu::Backends::GL,
@@ -87,9 +105,19 @@ impl<'a> State<'a> {
         let brush = BrushBuilder::using_font_bytes(include_bytes!("resources/consolas.TTF")).unwrap()
                 .build(&device, config.width, config.height, config.format);
 
+        let mut arena = MeshArena::new(&device)?;
+        log::info!("world seed: {}", app_config.seed);
+        let mut chunks = Chunks::new(&app_config, terrain_config)?;
+
+        let spawn_height = match chunks.load_surface(0, 0) {
+            Some(ground) => ground as f32 + 1.0 + SPAWN_EYE_HEIGHT,
+            None => FALLBACK_SPAWN_HEIGHT,
+        };
+        chunks.position = cgmath::Vector3::new(0.5, spawn_height, 0.5);
+
         let mut camera_uniform = CameraUniform::new();
         let camera = PlayerCamera::new(
-            cgmath::Point3::new(0.0, 20.0, 0.0),
+            cgmath::Point3::new(0.5, spawn_height, 0.5),
             cgmath::Deg(-90.0).into(),
             cgmath::Deg(-20.0).into(),
             config.width as f32 / config.height as f32,
@@ -104,11 +132,12 @@ impl<'a> State<'a> {
 
         let render_pipeline = pipeline::create_voxel_pipeline(
             &device,
-            &[&camera.layout],
+            &[&camera.layout, &arena.chunk_layout],
+        );
+        let transparent_pipeline = pipeline::create_transparent_pipeline(
+            &device,
+            &[&camera.layout, &arena.chunk_layout],
         );
-
-        let mut arena = MeshArena::new();
-        let mut chunks = Chunks::new();
 
         chunks.update_load_data_queue();
         chunks.update_load_mesh_queue();
@@ -120,7 +149,7 @@ impl<'a> State<'a> {
             r: 0.2, g: 0.4, b: 0.8, a: 1.0
         };
 
-        Self {
+        Ok(Self {
             window, 
             surface, 
             device, 
@@ -128,6 +157,7 @@ impl<'a> State<'a> {
             config, 
             size,
             render_pipeline,
+            transparent_pipeline,
             depth_texture,
             camera_uniform,
             camera,
@@ -135,6 +165,7 @@ impl<'a> State<'a> {
 		
             sky_color,
             chunks,
+            app_config,
             brush,
             arena,
 
@@ -142,7 +173,7 @@ impl<'a> State<'a> {
             cursor_grabbed: false,
             
             framecount: 0
-        }
+        })
     }
 
     pub fn window(&self) -> &Window {
@@ -185,6 +216,14 @@ impl<'a> State<'a> {
                         // reload chunks
                         self.run_chunk_loop();
                     }
+
+                    if *key == VirtualKeyCode::I && *state == ElementState::Pressed {
+                        self.inspect_surroundings();
+                    }
+
+                    if *key == VirtualKeyCode::F5 && *state == ElementState::Pressed {
+                        self.reload_terrain();
+                    }
                 }
                 true
             },
@@ -194,6 +233,19 @@ impl<'a> State<'a> {
                 ..
             } => {
                 self.mouse_pressed = *state == ElementState::Pressed;
+                if self.mouse_pressed && self.cursor_grabbed {
+                    self.break_voxel();
+                }
+                true
+            },
+            WindowEvent::MouseInput {
+                button: MouseButton::Right,
+                state: ElementState::Pressed,
+                ..
+            } => {
+                if self.cursor_grabbed {
+                    self.place_voxel();
+                }
                 true
             },
             _ => false,
@@ -215,6 +267,7 @@ impl<'a> State<'a> {
         );
 
         self.chunks.position = (self.camera.position.x, self.camera.position.y, self.camera.position.z).into();
+        self.chunks.sort_transparent_faces(&self.queue, &self.arena);
 
         if self.framecount == CHUNK_UPDATE_RATE {
             self.run_chunk_loop();
@@ -230,10 +283,78 @@ impl<'a> State<'a> {
 
         self.chunks.build_chunk_data_in_queue();
         self.chunks.build_chunk_meshes_in_queue(&self.device, &mut self.arena);
+        self.chunks.remesh_dirty_chunks();
         self.chunks.unload_data_queue();
         self.chunks.unload_mesh_queue(&mut self.arena);
     }
 
+    // rereads resources/terrain.ron and regenerates the world with it
+    fn reload_terrain(&mut self) {
+        let result = TerrainConfig::load()
+            .and_then(|terrain_config| self.chunks.regenerate(&self.app_config, &terrain_config, &mut self.arena));
+
+        match result {
+            Ok(()) => {
+                log::info!("terrain config reloaded");
+                self.run_chunk_loop();
+            }
+            Err(err) => log::error!("failed to reload terrain config: {err:#}"),
+        }
+    }
+
+    fn break_voxel(&mut self) {
+        let origin = cgmath::Vector3::new(self.camera.position.x, self.camera.position.y, self.camera.position.z);
+        if let Some((hit, _)) = self.chunks.raycast(origin, self.camera.forward(), EDIT_REACH) {
+            let _ = self.chunks.set_voxel(&hit, Voxel::new());
+        }
+    }
+
+    fn place_voxel(&mut self) {
+        let Some(id) = BlockRegistry::get().id(PLACE_BLOCK) else {
+            return;
+        };
+
+        let origin = cgmath::Vector3::new(self.camera.position.x, self.camera.position.y, self.camera.position.z);
+        if let Some((hit, front)) = self.chunks.raycast(origin, self.camera.forward(), EDIT_REACH) {
+            // a ray starting inside a block has no free cell in front of it, and the camera's
+            // own cell stays free
+            let free = self.chunks.try_get_voxel(&front).is_ok_and(|v| v.id == VoxelId::EMPTY);
+            if !free || front == WorldPos::containing(origin) {
+                return;
+            }
+
+            // orient the block along the face it was placed against
+            let state = match Face::from_normal(front.0 - hit.0) {
+                Some(face) => BlockState::default().with_facing(face).with_axis(face.axis()),
+                None => BlockState::default(),
+            };
+            let _ = self.chunks.set_voxel(&front, Voxel::from_id(id).with_state(state));
+        }
+    }
+
+    // logs the blocks in the current chunk and the nearest solid block outside it
+    fn inspect_surroundings(&self) {
+        let registry = BlockRegistry::get();
+        let origin = cgmath::Vector3::new(self.camera.position.x, self.camera.position.y, self.camera.position.z);
+        let position = WorldPos::containing(origin);
+
+        let counts = self.chunks.count_blocks(&Region::Box(Aabb::chunk(&position.chunk())));
+        let mut blocks = counts.value.iter()
+            .map(|(id, count)| format!("{}: {}", registry.block(*id).name, count))
+            .collect::<Vec<_>>();
+        blocks.sort();
+        log::info!("chunk {:?}: {}", *position.chunk(), blocks.join(", "));
+
+        let nearest = self.chunks.find_nearest(position, INSPECT_RADIUS, |voxel| voxel.is_solid());
+        match nearest.value {
+            Some(pos) => log::info!("nearest solid block at {:?}", *pos),
+            None => log::info!("no solid block within {INSPECT_RADIUS}"),
+        }
+        if !nearest.unloaded.is_empty() {
+            log::warn!("{} chunks in range are not loaded", nearest.unloaded.len());
+        }
+    }
+
     fn grab_cursor(&mut self) {
         self.cursor_grabbed = !self.cursor_grabbed;
         if self.cursor_grabbed {
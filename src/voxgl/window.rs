@@ -5,11 +5,37 @@ use winit::{
     window::WindowBuilder, dpi::PhysicalSize,
 };
 
-use super::{state::State, camera::player_camera::{MAX_VERTICAL_FOV, DEFAULT_VERTICAL_FOV}};
+use super::{config::Config, state::State, world::{chunk::ChunkDims, registry::BlockRegistry, terrain_config::TerrainConfig}, camera::player_camera::{MAX_VERTICAL_FOV, DEFAULT_VERTICAL_FOV}};
 
 pub async fn run() {
     env_logger::init();
 
+    let config = match Config::load() {
+        Ok(config) => config,
+        Err(err) => {
+            eprintln!("failed to load config: {err:#}");
+            return;
+        }
+    };
+
+    if let Err(err) = BlockRegistry::load().and_then(BlockRegistry::init) {
+        eprintln!("failed to load blocks: {err:#}");
+        return;
+    }
+
+    if let Err(err) = config.chunk_dims().and_then(ChunkDims::init) {
+        eprintln!("invalid chunk dimensions: {err:#}");
+        return;
+    }
+
+    let terrain_config = match TerrainConfig::load() {
+        Ok(terrain_config) => terrain_config,
+        Err(err) => {
+            eprintln!("failed to load terrain config: {err:#}");
+            return;
+        }
+    };
+
     let event_loop = EventLoop::new();
     let window = WindowBuilder::new()
         .with_max_inner_size(PhysicalSize::<i32>::new(800, 800))
@@ -18,7 +44,13 @@ pub async fn run() {
         .build(&event_loop)
         .unwrap();
 
-    let mut state = State::new(window).await;
+    let mut state = match State::new(window, config, &terrain_config).await {
+        Ok(state) => state,
+        Err(err) => {
+            eprintln!("failed to start: {err:#}");
+            return;
+        }
+    };
     let mut frame_count = 0;
     let mut last_render_time = std::time::Instant::now(); 
 
@@ -90,10 +122,13 @@ pub async fn run() {
                     Err(e) => eprintln!("{:?}", e),
                 }
                 
-                let pos = format!("X: {0:.2}, Y: {1:.2}, Z: {2:.2}", 
+                let pos = format!("X: {0:.2}, Y: {1:.2}, Z: {2:.2}\nSeed: {3}\nBiome: {4}", 
                     state.camera.position.x,
                     state.camera.position.y,
-                    state.camera.position.z
+                    state.camera.position.z,
+                    state.chunks.seed(),
+                    state.chunks.biome_at(state.camera.position.x.floor() as i32, state.camera.position.z.floor() as i32)
+                        .map_or("none", |biome| biome.name())
                 );
                 
                 let section = Section::default()
@@ -0,0 +1,151 @@
+use opensimplex_noise_rs::OpenSimplexNoise;
+use serde::Deserialize;
+
+use super::terrain_config::{BiomeShapes, TerrainConfig};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Biome {
+    Desert,
+    Plains,
+    Mountains,
+    Tundra,
+    Beach,
+}
+
+// Shape of a biome's terrain: columns sit at `base_height` plus up to `amplitude` of noise.
+#[derive(Debug, Clone, Copy, Deserialize)]
+pub struct HeightParams {
+    pub base_height: f64,
+    pub amplitude: f64,
+}
+
+// Biome picked by climate, centred on a (temperature, humidity) point in -1..1.
+// Beaches are not listed; they are placed by height afterwards.
+struct ClimateBiome {
+    biome: Biome,
+    temperature: f64,
+    humidity: f64,
+    height: fn(&BiomeShapes) -> HeightParams,
+}
+
+const CLIMATE_BIOMES: [ClimateBiome; 4] = [
+    ClimateBiome {
+        biome: Biome::Desert, temperature: 0.6, humidity: -0.5,
+        height: |shapes| shapes.desert,
+    },
+    ClimateBiome {
+        biome: Biome::Plains, temperature: 0.2, humidity: 0.4,
+        height: |shapes| shapes.plains,
+    },
+    ClimateBiome {
+        biome: Biome::Mountains, temperature: -0.2, humidity: -0.6,
+        height: |shapes| shapes.mountains,
+    },
+    ClimateBiome {
+        biome: Biome::Tundra, temperature: -0.6, humidity: 0.3,
+        height: |shapes| shapes.tundra,
+    },
+];
+
+impl Biome {
+    pub const ALL: [Biome; 5] = [Biome::Desert, Biome::Plains, Biome::Mountains, Biome::Tundra, Biome::Beach];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Desert => "desert",
+            Self::Plains => "plains",
+            Self::Mountains => "mountains",
+            Self::Tundra => "tundra",
+            Self::Beach => "beach",
+        }
+    }
+
+    // color of the biome on imported biome maps
+    pub fn map_color(&self) -> [u8; 3] {
+        match self {
+            Self::Desert => [237, 201, 100],
+            Self::Plains => [80, 170, 40],
+            Self::Mountains => [128, 128, 128],
+            Self::Tundra => [255, 255, 255],
+            Self::Beach => [250, 240, 180],
+        }
+    }
+
+    // biome whose map color is closest to a pixel
+    pub fn from_map_color(color: [u8; 3]) -> Biome {
+        let distance = |biome: &Biome| {
+            biome.map_color().iter().zip(color).map(|(a, b)| (*a as i32 - b as i32).pow(2)).sum::<i32>()
+        };
+        Self::ALL.into_iter().min_by_key(distance).unwrap_or(Biome::Plains)
+    }
+
+    // (surface, sub-surface) block names
+    pub fn blocks(&self) -> (&'static str, &'static str) {
+        match self {
+            Self::Desert => ("sand", "sand"),
+            Self::Plains => ("grass", "dirt"),
+            Self::Mountains => ("stone", "stone"),
+            Self::Tundra => ("snow", "dirt"),
+            Self::Beach => ("sand", "sand"),
+        }
+    }
+}
+
+// Climate noise and the blend of biomes it produces at each column.
+pub struct BiomeMap {
+    temperature: OpenSimplexNoise,
+    humidity: OpenSimplexNoise,
+    // frequency of the climate noise, lower means larger biomes
+    scale: f64,
+    // higher values make biome borders sharper
+    sharpness: i32,
+    heights: [HeightParams; CLIMATE_BIOMES.len()],
+}
+
+// How much each climate biome contributes to one column; weights sum to one.
+pub struct BiomeWeights {
+    weights: [(Biome, HeightParams, f64); CLIMATE_BIOMES.len()],
+}
+
+impl BiomeMap {
+    pub fn new(seed: u64, config: &TerrainConfig) -> Self {
+        Self {
+            temperature: OpenSimplexNoise::new(Some(seed.wrapping_add(1) as i64)),
+            humidity: OpenSimplexNoise::new(Some(seed.wrapping_add(2) as i64)),
+            scale: config.climate_scale,
+            sharpness: config.blend_sharpness,
+            heights: CLIMATE_BIOMES.map(|climate| (climate.height)(&config.biomes)),
+        }
+    }
+
+    pub fn weights(&self, x: f64, z: f64) -> BiomeWeights {
+        let temperature = self.temperature.eval_2d(x * self.scale, z * self.scale);
+        let humidity = self.humidity.eval_2d(x * self.scale, z * self.scale);
+
+        // inverse distance weighting in climate space keeps heights continuous across borders
+        let mut weights: [_; CLIMATE_BIOMES.len()] = std::array::from_fn(|index| {
+            let climate = &CLIMATE_BIOMES[index];
+            let distance2 = (temperature - climate.temperature).powi(2) + (humidity - climate.humidity).powi(2);
+            (climate.biome, self.heights[index], 1.0 / (distance2 + 1e-6).powf(self.sharpness as f64 / 2.0))
+        });
+
+        let total: f64 = weights.iter().map(|(_, _, w)| w).sum();
+        weights.iter_mut().for_each(|(_, _, w)| *w /= total);
+        BiomeWeights { weights }
+    }
+}
+
+impl BiomeWeights {
+    pub fn dominant(&self) -> Biome {
+        self.weights.iter()
+            .max_by(|a, b| a.2.total_cmp(&b.2))
+            .map_or(Biome::Plains, |(biome, _, _)| *biome)
+    }
+
+    // terrain height for a noise value in -1..1
+    pub fn height(&self, noise: f64) -> f64 {
+        self.weights.iter()
+            .map(|(_, height, weight)| (height.base_height + noise * height.amplitude) * weight)
+            .sum()
+    }
+}
@@ -1,17 +1,85 @@
-use cgmath::Vector3;
+use std::sync::OnceLock;
+use anyhow::{Context, Result};
+use cgmath::{InnerSpace, Vector3};
 use crate::voxgl::world::voxel::Voxel;
 use generational_arena::Index;
 
-use super::voxel::VoxelId;
+use super::{palette::PalettedVoxels, coords::LocalPos};
 
-pub const CHUNK_SIZE: usize = 16;
+pub const DEFAULT_CHUNK_SIZE: usize = 16;
+const MIN_CHUNK_SIZE: usize = 4;
+const MAX_CHUNK_SIZE: usize = 64;
 
-lazy_static::lazy_static! {
-    pub static ref BIT_SIZE: i32 = (CHUNK_SIZE as f32).log2() as i32;
+static CHUNK_DIMS: OnceLock<ChunkDims> = OnceLock::new();
+
+// tests run on their own threads, so each can try other dimensions without touching the global
+#[cfg(test)]
+thread_local! {
+    static TEST_DIMS: std::cell::Cell<Option<ChunkDims>> = const { std::cell::Cell::new(None) };
+}
+
+// Edge lengths of every chunk, picked once at startup. `size` is the horizontal (x/z) edge and
+// `height` the vertical one; both are powers of two so voxel indices can be built with shifts.
+#[derive(Debug, Clone, Copy)]
+pub struct ChunkDims {
+    pub size: i32,
+    pub height: i32,
+    size_bits: i32,
+    height_bits: i32,
+}
+
+impl ChunkDims {
+    pub fn new(size: usize, height: usize) -> Result<Self> {
+        for edge in [size, height] {
+            if !edge.is_power_of_two() || !(MIN_CHUNK_SIZE..=MAX_CHUNK_SIZE).contains(&edge) {
+                anyhow::bail!("chunk edge {edge} must be a power of two in {MIN_CHUNK_SIZE}..={MAX_CHUNK_SIZE}");
+            }
+        }
+
+        Ok(Self {
+            size: size as i32,
+            height: height as i32,
+            size_bits: size.trailing_zeros() as i32,
+            height_bits: height.trailing_zeros() as i32,
+        })
+    }
+
+    // installs the dimensions used by the world; must run before any chunk is created
+    pub fn init(dims: ChunkDims) -> Result<()> {
+        log::info!("chunk size: {}x{}x{}", dims.size, dims.height, dims.size);
+        CHUNK_DIMS.set(dims).ok().context("chunk dimensions already initialized")
+    }
+
+    pub fn get() -> ChunkDims {
+        #[cfg(test)]
+        if let Some(dims) = TEST_DIMS.with(|dims| dims.get()) {
+            return dims;
+        }
+        *CHUNK_DIMS.get_or_init(|| {
+            Self::new(DEFAULT_CHUNK_SIZE, DEFAULT_CHUNK_SIZE).expect("default chunk size is valid")
+        })
+    }
+
+    // runs `f` with `dims` in place of the world's dimensions on this thread
+    #[cfg(test)]
+    pub fn with<R>(dims: ChunkDims, f: impl FnOnce() -> R) -> R {
+        TEST_DIMS.with(|test_dims| test_dims.set(Some(dims)));
+        let result = f();
+        TEST_DIMS.with(|test_dims| test_dims.set(None));
+        result
+    }
+
+    pub fn extent(&self) -> Vector3<i32> {
+        Vector3::new(self.size, self.height, self.size)
+    }
+
+    pub fn volume(&self) -> usize {
+        (self.size * self.size * self.height) as usize
+    }
 }
 
 pub struct ChunkData {
-    pub voxels: [Voxel; CHUNK_SIZE * CHUNK_SIZE * CHUNK_SIZE],
+    pub voxels: PalettedVoxels,
 }
 
 impl lifeguard::Recycleable for ChunkData {
@@ -20,69 +88,116 @@ impl lifeguard::Recycleable for ChunkData {
     }
 
     fn reset(&mut self) {
-        for voxel in self.voxels.iter_mut() {
-            voxel.id = VoxelId::Empty;
-        }
+        self.voxels.fill(Voxel::new());
     }
 }
 
 impl ChunkData {
     pub fn new() -> Self {
         Self {
-            voxels: [Voxel::new(); CHUNK_SIZE * CHUNK_SIZE * CHUNK_SIZE],
+            voxels: PalettedVoxels::new(ChunkDims::get().volume(), Voxel::new()),
         }
     }
 
-    pub fn get_voxel(&self, pos: &Vector3<i32>) -> Option<&Voxel> {
-        self.voxels.get(Self::get_index(pos.x, pos.y, pos.z))
+    pub fn get_voxel(&self, pos: &LocalPos) -> Option<&Voxel> {
+        if !pos.is_valid() {
+            return None;
+        }
+        self.voxels.get(Self::get_index(pos))
     }
 
-    pub fn get_index(x: i32, y: i32, z: i32) -> usize {
-        (z | (y << *BIT_SIZE) | (x << (*BIT_SIZE * 2))) as usize
+    pub fn set_voxel(&mut self, pos: &LocalPos, voxel: Voxel) {
+        if pos.is_valid() {
+            self.voxels.set(Self::get_index(pos), voxel);
+        }
     }
 
-    pub fn get_local_pos(index: i32) -> Vector3<i32> {
-        Vector3 {
-            x: (index as f32 / (CHUNK_SIZE * CHUNK_SIZE) as f32) as i32,
-            y: ((index as f32 / CHUNK_SIZE as f32) % CHUNK_SIZE as f32) as i32,
-            z: (index as f32 % CHUNK_SIZE as f32) as i32,
-        }
+    pub fn get_index(pos: &LocalPos) -> usize {
+        let dims = ChunkDims::get();
+        (pos.z | (pos.y << dims.size_bits) | (pos.x << (dims.size_bits + dims.height_bits))) as usize
     }
 }
 
-pub struct ChunkMesh {
+#[derive(Default)]
+pub struct MeshBuffers {
     pub vertex_buffer: Option<Index>,
     pub index_buffer: Option<Index>,
     pub index_count: u32,
     pub vertex_count: u32,
 }
 
+impl MeshBuffers {
+    pub fn update_mesh_buffers(&mut self, v_buf: Index, i_buf: Index, v_count: u32, i_count: u32) {
+        self.vertex_buffer = Some(v_buf);
+        self.index_buffer = Some(i_buf);
+        self.vertex_count = v_count;
+        self.index_count = i_count;
+    }
+}
+
+// Faces of a transparent mesh, kept so its index buffer can be re-sorted as the camera moves.
+#[derive(Default)]
+pub struct TransparentFaces {
+    // world position of the face centre and the six indices drawing it
+    faces: Vec<(Vector3<f32>, [u32; 6])>,
+}
+
+impl TransparentFaces {
+    pub fn new(centers: &[Vector3<f32>], indices: &[u32]) -> Self {
+        let faces = centers.iter()
+            .zip(indices.chunks_exact(6))
+            .map(|(center, quad)| (*center, [quad[0], quad[1], quad[2], quad[3], quad[4], quad[5]]))
+            .collect();
+        Self { faces }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.faces.is_empty()
+    }
+
+    // indices ordered back to front from `eye`, so blended faces cover the ones behind them
+    pub fn sorted_indices(&self, eye: Vector3<f32>) -> Vec<u32> {
+        let mut faces: Vec<_> = self.faces.iter()
+            .map(|(center, quad)| ((center - eye).magnitude2(), quad))
+            .collect();
+        faces.sort_by(|a, b| b.0.total_cmp(&a.0));
+        faces.into_iter().flat_map(|(_, quad)| *quad).collect()
+    }
+}
+
+// Transparent blocks (water, glass) are meshed apart from opaque ones so they can be blended
+// over them in a second pass.
+pub struct ChunkMesh {
+    pub opaque: MeshBuffers,
+    pub transparent: MeshBuffers,
+    pub transparent_faces: TransparentFaces,
+    // uniform with the chunk origin and the bind group both meshes are drawn with
+    pub origin_buffer: Option<Index>,
+    pub bind_group: Option<Index>,
+}
+
 impl lifeguard::Recycleable for ChunkMesh {
     fn new() -> Self {
         ChunkMesh::new()
     }
 
     fn reset(&mut self) {
-        self.vertex_buffer = None;
-        self.index_buffer = None;
-        self.index_count = 0;
+        self.opaque = MeshBuffers::default();
+        self.transparent = MeshBuffers::default();
+        self.transparent_faces = TransparentFaces::default();
+        self.origin_buffer = None;
+        self.bind_group = None;
     }
 }
 
 impl ChunkMesh {
     pub fn new() -> Self {
         Self {
-            vertex_buffer: None,
-            index_buffer: None,
-            vertex_count: 0,
-            index_count: 0,
+            opaque: MeshBuffers::default(),
+            transparent: MeshBuffers::default(),
+            transparent_faces: TransparentFaces::default(),
+            origin_buffer: None,
+            bind_group: None,
         }
     }
-    
-    pub fn update_mesh_buffers(&mut self, v_buf: Index, i_buf: Index, v_count: u32, i_count: u32) {
-        self.vertex_buffer = Some(v_buf);
-        self.index_buffer = Some(i_buf);
-        self.vertex_count = v_count;
-        self.index_count = i_count;
-    }
 }
\ No newline at end of file
@@ -1,42 +1,76 @@
-use std::collections::{HashMap, VecDeque};
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::sync::Arc;
 use anyhow::{*, Context};
 use cgmath::{InnerSpace, Vector3};
 use lifeguard::{StartingSize, Pool, pool};
-use crate::voxgl::world::chunk::{ChunkData, ChunkMesh, CHUNK_SIZE};
-use crate::voxgl::world::mesh_builder::{self};
+use crate::voxgl::world::chunk::{ChunkData, ChunkDims, ChunkMesh, MeshBuffers};
+use crate::voxgl::world::mesh_builder::{self, ChunkSnapshot, MesherKind};
 use crate::voxgl::rendering::arena::MeshArena;
 use crate::voxgl::world::voxel::Voxel;
+use crate::voxgl::world::generator::WorldGenerator;
+use crate::voxgl::world::terrain_config::TerrainConfig;
+use crate::voxgl::world::workers::{GenerationWorkers, MeshWorkers};
+use crate::voxgl::config::Config;
+use crate::voxgl::world::biome::Biome;
+use crate::voxgl::world::structures;
+use crate::voxgl::world::coords::{ChunkPos, LocalPos, WorldPos};
+use crate::voxgl::world::storage::VoxelStorage;
+use crate::voxgl::world::heightmap::{Heightmap, Heightmaps};
 
 pub const RENDER_DISTANCE: i32 = 8;
 
 pub const MAX_DATA_LOAD: usize = 10_000;
 pub const MAX_MESH_LOAD: usize = 10_000;
 
-pub const MAX_DATA_LOAD_QUEUE: usize = 8;
+// chunks waiting for or being generated on the workers at once; higher keeps more workers busy
+pub const MAX_DATA_LOAD_QUEUE: usize = 16;
+// chunks waiting for or being meshed on the workers at once
 pub const MAX_MESH_LOAD_QUEUE: usize = 4;
+// chunks unloaded per chunk loop
 pub const MAX_DATA_UNLOAD_QUEUE: usize = 8;
 pub const MAX_MESH_UNLOAD_QUEUE: usize = 4;
 
+// vertical range searched for ground when picking a spawn point
+const SPAWN_SEARCH_TOP: i32 = 128;
+const SPAWN_SEARCH_BOTTOM: i32 = -64;
+
 pub struct Chunks {
-    chunk_data_map: HashMap<cgmath::Vector3<i32>, ChunkData>,
-    chunk_mesh_map: HashMap<cgmath::Vector3<i32>, ChunkMesh>,
+    storage: Box<dyn VoxelStorage>,
+    heightmaps: Heightmaps,
+    chunk_mesh_map: HashMap<ChunkPos, ChunkMesh>,
 
     chunk_data_pool: Pool<ChunkData>,
     chunk_mesh_pool: Pool<ChunkMesh>,
 
-    chunk_data_load_queue: VecDeque<cgmath::Vector3<i32>>,
-    chunk_mesh_load_queue: VecDeque<cgmath::Vector3<i32>>,
-    chunk_data_unload_queue: VecDeque<cgmath::Vector3<i32>>,
-    chunk_mesh_unload_queue: VecDeque<cgmath::Vector3<i32>>,
-
+    chunk_data_load_queue: VecDeque<ChunkPos>,
+    chunk_mesh_load_queue: VecDeque<ChunkPos>,
+    chunk_data_unload_queue: VecDeque<ChunkPos>,
+    chunk_mesh_unload_queue: VecDeque<ChunkPos>,
+    dirty_chunks: HashSet<ChunkPos>,
+    // structure writes of each loaded chunk that land in its neighbours, replayed into a
+    // neighbour whenever it is generated and dropped when the chunk unloads
+    structure_writes: HashMap<ChunkPos, Vec<(WorldPos, Voxel)>>,
+
+    seed: u64,
+    world: Arc<WorldGenerator>,
+    workers: GenerationWorkers,
+    mesh_workers: MeshWorkers,
+    mesher: MesherKind,
     render_distance: i32,
     pub position: cgmath::Vector3<f32>,
+    // chunk the camera was in when transparent faces were last sorted
+    faces_sorted_from: Option<ChunkPos>,
+    // last column looked up by `biome_at`; the lookup may run erosion, so it is not repeated
+    // every frame while the camera stays in one column
+    biome_cache: Option<((i32, i32), Option<Biome>)>,
 }
 
 impl Chunks {
-    pub fn new() -> Self {
+    pub fn new(config: &Config, terrain: &TerrainConfig) -> Result<Self> {
+        log::info!("voxel storage: {:?}, terrain: {:?}, mesher: {:?}", config.storage, config.terrain, config.mesher);
         let chunks = Self {
-            chunk_data_map: HashMap::with_capacity(MAX_DATA_LOAD),
+            storage: config.storage.create(),
+            heightmaps: Heightmaps::new(),
             chunk_mesh_map: HashMap::with_capacity(MAX_MESH_LOAD),
             
             chunk_data_pool: pool().with(StartingSize(MAX_DATA_LOAD)).build(),
@@ -46,87 +80,418 @@ impl Chunks {
             chunk_mesh_load_queue: VecDeque::with_capacity(MAX_MESH_LOAD_QUEUE),
             chunk_data_unload_queue: VecDeque::with_capacity(MAX_DATA_UNLOAD_QUEUE),
             chunk_mesh_unload_queue: VecDeque::with_capacity(MAX_MESH_UNLOAD_QUEUE),
+            dirty_chunks: HashSet::new(),
+            structure_writes: HashMap::new(),
             
+            seed: config.seed,
+            world: Arc::new(WorldGenerator::new(config, terrain)?),
+            workers: GenerationWorkers::new(),
+            mesh_workers: MeshWorkers::new(),
+            mesher: config.mesher,
             position: cgmath::Vector3::<f32>::new(0., 0., 0.),
+            faces_sorted_from: None,
+            biome_cache: None,
             render_distance: RENDER_DISTANCE,
         };
-        chunks
+        Ok(chunks)
     }
 
+    // swaps in a generator built from the new terrain config and drops every chunk, including
+    // edits, so the world regenerates around the player; keeps the old world if the config fails
+    pub fn regenerate(&mut self, config: &Config, terrain: &TerrainConfig, arena: &mut MeshArena) -> Result<()> {
+        self.world = Arc::new(WorldGenerator::new(config, terrain)?);
+        self.workers.cancel_all();
+        self.mesh_workers.cancel_all();
+
+        for (_, mut chunk_mesh) in self.chunk_mesh_map.drain() {
+            release_mesh_buffers(&mut chunk_mesh, arena);
+            self.chunk_mesh_pool.attach(chunk_mesh);
+        }
+        for chunk_data in self.storage.drain() {
+            self.chunk_data_pool.attach(chunk_data);
+        }
+        self.heightmaps = Heightmaps::new();
+
+        self.chunk_data_load_queue.clear();
+        self.chunk_mesh_load_queue.clear();
+        self.chunk_data_unload_queue.clear();
+        self.chunk_mesh_unload_queue.clear();
+        self.dirty_chunks.clear();
+        self.structure_writes.clear();
+        self.biome_cache = None;
+        Ok(())
+    }
+
+    // hands queued chunks to the workers and stores the ones they have finished
     pub fn build_chunk_data_in_queue(&mut self) {
         while let Some(chunk_pos) = self.chunk_data_load_queue.pop_front() {
-            self.build_chunk_data(chunk_pos);
+            let chunk = self.chunk_data_pool.detached();
+            self.workers.submit(chunk_pos, chunk, &self.world);
+        }
+
+        for generated in self.workers.collect() {
+            self.insert_generated(generated.chunk_pos, generated.chunk, generated.structures);
+        }
+        for chunk_data in self.workers.take_discarded() {
+            self.chunk_data_pool.attach(chunk_data);
+        }
+    }
+
+    pub fn try_get_voxel(&self, world_pos: &WorldPos) -> Result<&Voxel> {
+        let (chunk_pos, local_pos) = world_pos.split();
+        self.storage.get_voxel(&chunk_pos, &local_pos).context("no voxel")
+    }
+
+    pub fn set_voxel(&mut self, world_pos: &WorldPos, voxel: Voxel) -> Result<()> {
+        self.set_voxels([(*world_pos, voxel)])
+    }
+
+    // applies every edit or none of them if any target chunk is not loaded
+    pub fn set_voxels(&mut self, edits: impl IntoIterator<Item = (WorldPos, Voxel)>) -> Result<()> {
+        let edits = edits.into_iter()
+            .map(|(world_pos, voxel)| (world_pos.split(), voxel))
+            .collect::<Vec<_>>();
+
+        if let Some(((chunk_pos, _), _)) = edits.iter().find(|((p, _), _)| !self.storage.contains(p)) {
+            bail!("chunk {:?} is not loaded", chunk_pos);
+        }
+
+        for ((chunk_pos, local_pos), voxel) in edits {
+            if self.storage.set_voxel(&chunk_pos, &local_pos, voxel) {
+                self.update_heightmap(&chunk_pos, &local_pos, &voxel);
+                self.mark_dirty(&chunk_pos, &local_pos);
+            }
+        }
+        Ok(())
+    }
+
+    fn update_heightmap(&mut self, chunk_pos: &ChunkPos, local_pos: &LocalPos, voxel: &Voxel) {
+        let Some(heightmap) = self.heightmaps.get_mut(chunk_pos) else {
+            return;
+        };
+
+        let top = heightmap.top(local_pos.x, local_pos.z);
+        if voxel.is_solid() {
+            if top.is_none_or(|top| local_pos.y > top) {
+                heightmap.set_top(local_pos.x, local_pos.z, Some(local_pos.y));
+            }
+        } else if top == Some(local_pos.y) {
+            // the top voxel was removed, look for the next solid one underneath
+            let storage = &self.storage;
+            let new_top = (0..local_pos.y).rev().find(|y| {
+                storage.get_voxel(chunk_pos, &LocalPos::new(local_pos.x, *y, local_pos.z)).is_some_and(|v| v.is_solid())
+            });
+            heightmap.set_top(local_pos.x, local_pos.z, new_top);
+        }
+    }
+
+    // y of the highest solid voxel in the world column at (x, z), among loaded chunks
+    pub fn surface_height(&self, x: i32, z: i32) -> Option<i32> {
+        self.highest_solid(x, z, i32::MAX)
+    }
+
+    // y of the highest solid voxel strictly below `below` in the world column at (x, z)
+    pub fn highest_solid(&self, x: i32, z: i32, below: i32) -> Option<i32> {
+        let (start_chunk, start) = WorldPos(Vector3::new(x, below.saturating_sub(1), z)).split();
+        let chunk_height = ChunkDims::get().height;
+
+        for (chunk_y, heightmap) in self.heightmaps.column_below(&start_chunk) {
+            let Some(top) = heightmap.top(start.x, start.z) else {
+                continue;
+            };
+            if chunk_y < start_chunk.y || top <= start.y {
+                return Some(chunk_y * chunk_height + top);
+            }
+
+            // the column top is above the cutoff, so scan the voxels under it
+            let chunk_pos = ChunkPos(Vector3::new(start_chunk.x, chunk_y, start_chunk.z));
+            let found = (0..=start.y).rev().find(|y| {
+                self.storage.get_voxel(&chunk_pos, &LocalPos::new(start.x, *y, start.z)).is_some_and(|v| v.is_solid())
+            });
+            if let Some(y) = found {
+                return Some(chunk_y * chunk_height + y);
+            }
+        }
+        None
+    }
+
+    // generates the chunk column at (x, z) from the top down until it reaches the ground
+    pub fn load_surface(&mut self, x: i32, z: i32) -> Option<i32> {
+        let top = WorldPos(Vector3::new(x, SPAWN_SEARCH_TOP, z)).chunk();
+        let bottom = WorldPos(Vector3::new(x, SPAWN_SEARCH_BOTTOM, z)).chunk();
+
+        for chunk_y in (bottom.y..=top.y).rev() {
+            let chunk_pos = ChunkPos(Vector3::new(top.x, chunk_y, top.z));
+            if !self.storage.contains(&chunk_pos) {
+                self.build_chunk_data(chunk_pos);
+            }
+            if let Some(height) = self.surface_height(x, z) {
+                return Some(height);
+            }
+        }
+        None
+    }
+
+    // first solid voxel hit by the ray and the voxel in front of it
+    pub fn raycast(
+        &self, origin: Vector3<f32>, direction: Vector3<f32>, max_distance: f32
+    ) -> Option<(WorldPos, WorldPos)> {
+
+        let direction = direction.normalize();
+        let step = direction.map(|d| d.signum() as i32);
+        let t_delta = direction.map(|d| if d == 0.0 { f32::INFINITY } else { 1.0 / d.abs() });
+
+        let mut voxel_pos = WorldPos::containing(origin);
+        let mut t_max = Vector3::new(
+            next_boundary(origin.x, direction.x),
+            next_boundary(origin.y, direction.y),
+            next_boundary(origin.z, direction.z),
+        );
+
+        let mut previous = voxel_pos;
+        let mut travelled = 0.0;
+        while travelled <= max_distance {
+            if self.try_get_voxel(&voxel_pos).is_ok_and(|v| v.is_solid()) {
+                return Some((voxel_pos, previous));
+            }
+
+            previous = voxel_pos;
+            if t_max.x < t_max.y && t_max.x < t_max.z {
+                voxel_pos.0.x += step.x;
+                travelled = t_max.x;
+                t_max.x += t_delta.x;
+            } else if t_max.y < t_max.z {
+                voxel_pos.0.y += step.y;
+                travelled = t_max.y;
+                t_max.y += t_delta.y;
+            } else {
+                voxel_pos.0.z += step.z;
+                travelled = t_max.z;
+                t_max.z += t_delta.z;
+            }
+        }
+        None
+    }
+
+    // edits on a chunk border also change the faces meshed by the neighbouring chunks; ambient
+    // occlusion reaches diagonally, so edges and corners touch up to seven neighbours
+    fn mark_dirty(&mut self, chunk_pos: &ChunkPos, local_pos: &LocalPos) {
+        let extent = ChunkDims::get().extent();
+        let border = |axis: usize| match local_pos[axis] {
+            0 => -1,
+            p if p == extent[axis] - 1 => 1,
+            _ => 0,
+        };
+        let (bx, by, bz) = (border(0), border(1), border(2));
+
+        for x in [0, bx] {
+            for y in [0, by] {
+                for z in [0, bz] {
+                    self.dirty_chunks.insert(*chunk_pos + Vector3::new(x, y, z));
+                }
+            }
         }
     }
 
-    pub fn try_get_voxel(&self, chunk_pos: &Vector3<i32>, local_pos: &Vector3<i32>) -> Result<&Voxel> {
-        let mut chunk_pos = *chunk_pos;
-        let mut local_pos = *local_pos;
-        make_coords_valid(&mut chunk_pos, &mut local_pos);
+    // dirty chunks keep drawing their old mesh until the workers send the new one
+    pub fn remesh_dirty_chunks(&mut self) {
+        let dirty = std::mem::take(&mut self.dirty_chunks);
+        for chunk_pos in dirty {
+            if self.chunk_mesh_map.contains_key(&chunk_pos) {
+                self.submit_mesh(chunk_pos);
+            }
+        }
+    }
 
-        let chunk = self.chunk_data_map.get(&chunk_pos).context("no data")?;
-        chunk.get_voxel(&local_pos).context("no voxel")
+    pub fn seed(&self) -> u64 {
+        self.seed
     }
 
-    pub fn get_chunk_mesh_mut(&mut self, chunk_pos: &Vector3<i32>) -> Option<&mut ChunkMesh> {
-        self.chunk_mesh_map.get_mut(chunk_pos)
+    pub fn biome_at(&mut self, x: i32, z: i32) -> Option<Biome> {
+        if let Some((column, biome)) = self.biome_cache {
+            if column == (x, z) {
+                return biome;
+            }
+        }
+
+        let biome = self.world.terrain().map(|terrain| terrain.biome_at(x, z));
+        self.biome_cache = Some(((x, z), biome));
+        biome
     }
 
-    pub fn build_chunk_data(&mut self, chunk_pos: Vector3<i32>) {
+    // generates a chunk right away on the calling thread
+    fn build_chunk_data(&mut self, chunk_pos: ChunkPos) {
         let mut chunk = self.chunk_data_pool.detached();
-        let chunk_world_pos = chunk_to_world(&chunk_pos);
-        chunk.build_voxel_data(&chunk_world_pos);
-        self.chunk_data_map.insert(chunk_pos, chunk);
+        let structures = self.world.generate(&mut chunk, &chunk_pos);
+        self.insert_generated(chunk_pos, chunk, structures);
+    }
+
+    fn insert_generated(&mut self, chunk_pos: ChunkPos, mut chunk: ChunkData, structures: Vec<(WorldPos, Voxel)>) {
+        // already generated on the main thread while the workers had it
+        if self.storage.contains(&chunk_pos) {
+            self.chunk_data_pool.attach(chunk);
+            return;
+        }
+
+        // structures may reach into neighbours, which get the write now if they are loaded and
+        // again every time they are generated while this chunk stays loaded
+        let mut outside = Vec::new();
+        for (world_pos, voxel) in structures {
+            let (target, local_pos) = world_pos.split();
+            if target == chunk_pos {
+                structures::place_structure_voxel(&mut chunk, &local_pos, voxel);
+            } else {
+                if self.storage.contains(&target) {
+                    self.write_structure_voxel(&target, &local_pos, voxel);
+                }
+                outside.push((world_pos, voxel));
+            }
+        }
+        self.apply_neighbour_structures(&chunk_pos, &mut chunk);
+        if !outside.is_empty() {
+            self.structure_writes.insert(chunk_pos, outside);
+        }
+        chunk.voxels.compact();
+
+        self.heightmaps.insert(&chunk_pos, Heightmap::from_chunk(&chunk));
+        if let Some(unused) = self.storage.insert(chunk_pos, chunk) {
+            self.chunk_data_pool.attach(unused);
+        }
+    }
+
+    // parts of structures rooted in loaded neighbours that reach into `chunk`
+    fn apply_neighbour_structures(&self, chunk_pos: &ChunkPos, chunk: &mut ChunkData) {
+        let margin = structures::chunk_margin();
+        for x in -margin.x..=margin.x {
+            for y in -margin.y..=margin.y {
+                for z in -margin.z..=margin.z {
+                    let Some(writes) = self.structure_writes.get(&(*chunk_pos + Vector3::new(x, y, z))) else {
+                        continue;
+                    };
+                    for (world_pos, voxel) in writes {
+                        let (target, local_pos) = world_pos.split();
+                        if target == *chunk_pos {
+                            structures::place_structure_voxel(chunk, &local_pos, *voxel);
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    fn write_structure_voxel(&mut self, chunk_pos: &ChunkPos, local_pos: &LocalPos, voxel: Voxel) {
+        if self.storage.get_voxel(chunk_pos, local_pos).is_some_and(|v| v.is_visible()) {
+            return;
+        }
+        if self.storage.set_voxel(chunk_pos, local_pos, voxel) {
+            self.update_heightmap(chunk_pos, local_pos, &voxel);
+            self.mark_dirty(chunk_pos, local_pos);
+        }
+    }
+
+    // a chunk is meshed once every chunk that can write structures into it has been generated,
+    // which also covers the face neighbours needed to cull its border faces
+    fn is_neighbourhood_generated(&self, chunk_pos: &ChunkPos) -> bool {
+        let margin = structures::chunk_margin();
+        for x in -margin.x..=margin.x {
+            for y in -margin.y..=margin.y {
+                for z in -margin.z..=margin.z {
+                    if !self.storage.contains(&(*chunk_pos + Vector3::new(x, y, z))) {
+                        return false;
+                    }
+                }
+            }
+        }
+        true
     }
 
+    // hands queued chunks to the mesh workers and uploads the meshes they have finished
     pub fn build_chunk_meshes_in_queue(&mut self, device: &wgpu::Device, arena: &mut MeshArena) {
         while let Some(chunk_pos) = self.chunk_mesh_load_queue.pop_front() {
             if self.chunk_mesh_map.len() >= MAX_DATA_LOAD {
-                return;
+                break;
             }
             
             let chunk_mesh = self.chunk_mesh_pool.detached();
             self.chunk_mesh_map.insert(chunk_pos, chunk_mesh);
-            let chunk_world_pos = chunk_to_world(&chunk_pos);
-            
-            if mesh_builder::build_chunk_mesh(self, &chunk_pos, &chunk_world_pos, device, arena) {
-                return;
+            self.submit_mesh(chunk_pos);
+        }
+
+        for meshed in self.mesh_workers.collect() {
+            let Some(chunk_mesh) = self.chunk_mesh_map.get_mut(&meshed.chunk_pos) else {
+                continue;
+            };
+            release_buffers(&mut chunk_mesh.opaque, arena);
+            release_buffers(&mut chunk_mesh.transparent, arena);
+            mesh_builder::upload_chunk_mesh(chunk_mesh, &meshed.chunk_pos, meshed.data, self.position, device, arena);
+        }
+    }
+
+    // re-sorts transparent faces back to front once the camera enters another chunk; meshes
+    // uploaded in between are already sorted from close enough
+    pub fn sort_transparent_faces(&mut self, queue: &wgpu::Queue, arena: &MeshArena) {
+        let current_chunk_pos = ChunkPos::containing(self.position);
+        if self.faces_sorted_from == Some(current_chunk_pos) {
+            return;
+        }
+        self.faces_sorted_from = Some(current_chunk_pos);
+
+        for chunk_mesh in self.chunk_mesh_map.values() {
+            if chunk_mesh.transparent_faces.is_empty() {
+                continue;
             }
+            let Some(index_buffer) = chunk_mesh.transparent.index_buffer.and_then(|index| arena.buffer.get(index)) else {
+                continue;
+            };
+            let indices = chunk_mesh.transparent_faces.sorted_indices(self.position);
+            queue.write_buffer(index_buffer, 0, bytemuck::cast_slice(&indices));
         }
     }
 
-    pub fn is_chunk_busy(&self, chunk_pos: &Vector3<i32>) -> bool {
-        self.chunk_data_map.contains_key(chunk_pos) || self.chunk_data_load_queue.contains(chunk_pos)
+    fn submit_mesh(&mut self, chunk_pos: ChunkPos) {
+        let snapshot = ChunkSnapshot::capture(self, &chunk_pos);
+        self.mesh_workers.submit(chunk_pos, snapshot, self.mesher);
+    }
+
+    // chunks queued for meshing or still on the workers
+    fn mesh_load_pending(&self) -> usize {
+        self.chunk_mesh_load_queue.len() + self.mesh_workers.in_flight()
+    }
+
+    pub fn is_chunk_busy(&self, chunk_pos: &ChunkPos) -> bool {
+        self.storage.contains(chunk_pos)
+            || self.chunk_data_load_queue.contains(chunk_pos)
+            || self.workers.is_generating(chunk_pos)
+    }
+
+    pub fn is_chunk_loaded(&self, chunk_pos: &ChunkPos) -> bool {
+        self.storage.contains(chunk_pos)
     }
 
-    pub fn is_mesh_busy(&self, chunk_pos: &Vector3<i32>) -> bool {
+    pub fn is_mesh_busy(&self, chunk_pos: &ChunkPos) -> bool {
         self.chunk_mesh_map.contains_key(chunk_pos) || self.chunk_mesh_load_queue.contains(chunk_pos)
     }
 
-    pub fn in_range(&self, chunk_pos: Vector3<i32>) -> bool {
-        let chunk_world_pos = chunk_to_world(&chunk_pos);
+    pub fn in_range(&self, chunk_pos: ChunkPos) -> bool {
+        let chunk_world_pos = chunk_pos.world_origin();
         let delta = self.position - chunk_world_pos;
 
         let distance_sq: f32 = delta.magnitude2().into();
-        let render_dist = (self.render_distance as f32) * CHUNK_SIZE as f32;
+        let render_dist = (self.render_distance * ChunkDims::get().size) as f32;
         let render_distance_sq = render_dist * render_dist;
 
         distance_sq < render_distance_sq
     }
 
     pub fn update_load_mesh_queue(&mut self) {
-        if self.chunk_mesh_map.len() >= MAX_MESH_LOAD || self.chunk_mesh_load_queue.len() >= MAX_MESH_LOAD_QUEUE {
+        if self.chunk_mesh_map.len() >= MAX_MESH_LOAD || self.mesh_load_pending() >= MAX_MESH_LOAD_QUEUE {
             return;
         }
 
         for y in -self.render_distance..self.render_distance {
             for z in -self.render_distance..self.render_distance {
                 for x in -self.render_distance..self.render_distance {
-                    let current_chunk_pos = Vector3::<i32>::new(
-                        (self.position.x / CHUNK_SIZE as f32) as i32,
-                        (self.position.y / CHUNK_SIZE as f32) as i32,
-                        (self.position.z / CHUNK_SIZE as f32) as i32,
-                    );
+                    let current_chunk_pos = ChunkPos::containing(self.position);
                     let chunk_pos = current_chunk_pos + Vector3::<i32>::new(x, y, z);
 
                     if self.is_mesh_busy(&chunk_pos) {
@@ -134,23 +499,10 @@ impl Chunks {
                     }
 
                     let in_range = self.in_range(current_chunk_pos);
-                    use cgmath::Vector3 as vec;
-                    let adj_chunk_data_bad = [
-                        -vec::<i32>::unit_x(),
-                        vec::<i32>::unit_x(),
-                        -vec::<i32>::unit_y(),
-                        vec::<i32>::unit_y(),
-                        -vec::<i32>::unit_z(),
-                        vec::<i32>::unit_z(),
-                    ]
-                    .iter_mut()
-                    .map(|v| *v + chunk_pos)
-                    .any(|v| !self.chunk_data_map.contains_key(&v));
-
-                    if in_range && !adj_chunk_data_bad {
+                    if in_range && self.is_neighbourhood_generated(&chunk_pos) {
                         self.chunk_mesh_load_queue.push_back(chunk_pos);
 
-                        if self.chunk_mesh_load_queue.len() >= MAX_MESH_LOAD_QUEUE {
+                        if self.mesh_load_pending() >= MAX_MESH_LOAD_QUEUE {
                             return;
                         }
                     }
@@ -160,11 +512,7 @@ impl Chunks {
     }
 
     pub fn update_unload_data_queue(&mut self) {
-        let current_chunk_pos = Vector3::<i32>::new(
-            (self.position.x / CHUNK_SIZE as f32) as i32,
-            (self.position.y / CHUNK_SIZE as f32) as i32,
-            (self.position.z / CHUNK_SIZE as f32) as i32,
-        );
+        let current_chunk_pos = ChunkPos::containing(self.position);
 
         let outside = self.chunk_mesh_map.iter()
             .filter(|(p, _m)| {
@@ -191,11 +539,7 @@ impl Chunks {
     }
 
     pub fn update_unload_mesh_queue(&mut self) {
-        let current_chunk_pos = Vector3::<i32>::new(
-            (self.position.x / CHUNK_SIZE as f32) as i32,
-            (self.position.y / CHUNK_SIZE as f32) as i32,
-            (self.position.z / CHUNK_SIZE as f32) as i32,
-        );
+        let current_chunk_pos = ChunkPos::containing(self.position);
 
         let outside = self.chunk_mesh_map.iter()
             .filter(|(p, _m)| {
@@ -223,25 +567,23 @@ impl Chunks {
 
     pub fn unload_data_queue(&mut self) {
         while let Some(chunk_pos) = self.chunk_data_unload_queue.pop_front() {
-            if let Some(chunk_data) = self.chunk_data_map.remove(&chunk_pos) {
+            self.heightmaps.remove(&chunk_pos);
+            self.structure_writes.remove(&chunk_pos);
+            if let Some(chunk_data) = self.storage.remove(&chunk_pos) {
                 self.chunk_data_pool.attach(chunk_data);
             }
         }
     }
 
     pub fn update_load_data_queue(&mut self) {
-        if self.chunk_data_map.len() >= MAX_DATA_LOAD || self.chunk_data_load_queue.len() >= MAX_DATA_LOAD_QUEUE {
+        if self.storage.chunk_count() >= MAX_DATA_LOAD || self.data_load_pending() >= MAX_DATA_LOAD_QUEUE {
             return;
         }
 
         for y in -self.render_distance..self.render_distance {
             for z in -self.render_distance..self.render_distance {
                 for x in -self.render_distance..self.render_distance {
-                    let current_chunk_pos = Vector3::<i32>::new(
-                        (self.position.x / CHUNK_SIZE as f32) as i32,
-                        (self.position.y / CHUNK_SIZE as f32) as i32,
-                        (self.position.z / CHUNK_SIZE as f32) as i32,
-                    );
+                    let current_chunk_pos = ChunkPos::containing(self.position);
 
                     let chunk_pos = current_chunk_pos + Vector3::<i32>::new(x, y, z);
                     if self.is_chunk_busy(&chunk_pos) {
@@ -253,7 +595,7 @@ impl Chunks {
                         self.chunk_data_load_queue.push_back(chunk_pos);
                     }
 
-                    if self.chunk_data_load_queue.len() >= MAX_DATA_LOAD_QUEUE {
+                    if self.data_load_pending() >= MAX_DATA_LOAD_QUEUE {
                         return;
                     }
                 }
@@ -261,100 +603,131 @@ impl Chunks {
         }
     }
 
+    // chunks queued for generation or still on the workers
+    fn data_load_pending(&self) -> usize {
+        self.chunk_data_load_queue.len() + self.workers.in_flight()
+    }
+
     pub fn unload_mesh_queue(&mut self, arena: &mut MeshArena) {
         while let Some(chunk_pos) = self.chunk_mesh_unload_queue.pop_front() {
-            if let Some(chunk_mesh) = self.chunk_mesh_map.remove(&chunk_pos) {
-
-                if let Some(v_buf_key) = chunk_mesh.vertex_buffer {
-                    if let Some(v_buf) = arena.buffer.get_mut(v_buf_key) {
-                        v_buf.destroy();
-                    }
-                    arena.buffer.remove(v_buf_key);
-                }
-                
-                if let Some(i_buf_key) = chunk_mesh.index_buffer {
-                    if let Some(i_buf) = arena.buffer.get_mut(i_buf_key) {
-                        i_buf.destroy();
-                    }
-                    arena.buffer.remove(i_buf_key);
-                }
-                
+            self.mesh_workers.cancel(&chunk_pos);
+            if let Some(mut chunk_mesh) = self.chunk_mesh_map.remove(&chunk_pos) {
+                release_mesh_buffers(&mut chunk_mesh, arena);
                 self.chunk_mesh_pool.attach(chunk_mesh);
             }
         }
     }
 
-    pub fn draw<'a, 'b>(
-        &mut self, render_pass: &mut wgpu::RenderPass<'a>, camera_bind_group: &'a wgpu::BindGroup, arena: &'a MeshArena
+    pub fn draw_opaque<'a>(
+        &self, render_pass: &mut wgpu::RenderPass<'a>, camera_bind_group: &'a wgpu::BindGroup, arena: &'a MeshArena
     ) -> Result<()> {
+        for chunk in self.chunk_mesh_map.values() {
+            draw_mesh(render_pass, &chunk.opaque, chunk.bind_group, camera_bind_group, arena)?;
+        }
+        Ok(())
+    }
+
+    // blended over the opaque terrain, so chunks go back to front like the faces inside them
+    pub fn draw_transparent<'a>(
+        &self, render_pass: &mut wgpu::RenderPass<'a>, camera_bind_group: &'a wgpu::BindGroup, arena: &'a MeshArena
+    ) -> Result<()> {
+        let dims = ChunkDims::get();
+        let half_chunk = Vector3::new(dims.size, dims.height, dims.size).map(|c| c as f32 / 2.0);
 
-        for (_, chunk) in self.chunk_mesh_map.iter() {
-            let v_buf_index = chunk.vertex_buffer.as_ref().context("no vertices")?;
-            let i_buf_index = chunk.index_buffer.as_ref().context("no indices")?;
+        let mut chunks: Vec<_> = self.chunk_mesh_map.iter()
+            .filter(|(_, chunk)| chunk.transparent.index_count > 0)
+            .map(|(chunk_pos, chunk)| ((chunk_pos.world_origin() + half_chunk - self.position).magnitude2(), chunk))
+            .collect();
+        chunks.sort_by(|a, b| b.0.total_cmp(&a.0));
 
-            let vertex_buffer = arena.buffer.get(*v_buf_index).context("no v_buf_index")?;
-            let index_buffer = arena.buffer.get(*i_buf_index).context("no i_buf_index")?;
-            let _ = draw_chunk(
-                render_pass,
-                chunk.index_count,
-                camera_bind_group,
-                vertex_buffer,
-                index_buffer
-            );
+        for (_, chunk) in chunks {
+            draw_mesh(render_pass, &chunk.transparent, chunk.bind_group, camera_bind_group, arena)?;
         }
         Ok(())
     }
 
     pub fn get_vertex_count(&self) -> u32 {
         self.chunk_mesh_map.iter()
-            .map(|(_, chunk)| chunk.vertex_count)
+            .map(|(_, chunk)| chunk.opaque.vertex_count + chunk.transparent.vertex_count)
             .sum()
     }
 }
 
-fn make_coords_valid(chunk_pos: &mut Vector3<i32>, local_pos: &mut Vector3<i32>) {
-    let chunk_size = CHUNK_SIZE as i32;
-    while local_pos.x < 0 {
-        local_pos.x += chunk_size;
-        chunk_pos.x -= 1;
-    }
-    while local_pos.x > chunk_size {
-        local_pos.x -= chunk_size;
-        chunk_pos.x += 1;
+fn release_mesh_buffers(chunk_mesh: &mut ChunkMesh, arena: &mut MeshArena) {
+    release_buffers(&mut chunk_mesh.opaque, arena);
+    release_buffers(&mut chunk_mesh.transparent, arena);
+
+    if let Some(bind_group_key) = chunk_mesh.bind_group.take() {
+        arena.bind_group.remove(bind_group_key);
     }
-    while local_pos.y < 0 {
-        local_pos.y += chunk_size;
-        chunk_pos.y -= 1;
+    if let Some(origin_key) = chunk_mesh.origin_buffer.take() {
+        if let Some(origin) = arena.buffer.get_mut(origin_key) {
+            origin.destroy();
+        }
+        arena.buffer.remove(origin_key);
     }
-    while local_pos.y > chunk_size {
-        local_pos.y -= chunk_size;
-        chunk_pos.y += 1;
+}
+
+fn release_buffers(buffers: &mut MeshBuffers, arena: &mut MeshArena) {
+    if let Some(v_buf_key) = buffers.vertex_buffer.take() {
+        if let Some(v_buf) = arena.buffer.get_mut(v_buf_key) {
+            v_buf.destroy();
+        }
+        arena.buffer.remove(v_buf_key);
     }
-    while local_pos.z < 0 {
-        local_pos.z += chunk_size;
-        chunk_pos.z -= 1;
+    
+    if let Some(i_buf_key) = buffers.index_buffer.take() {
+        if let Some(i_buf) = arena.buffer.get_mut(i_buf_key) {
+            i_buf.destroy();
+        }
+        arena.buffer.remove(i_buf_key);
     }
-    while local_pos.z > chunk_size {
-        local_pos.z -= chunk_size;
-        chunk_pos.z += 1;
+    buffers.index_count = 0;
+    buffers.vertex_count = 0;
+}
+
+fn next_boundary(origin: f32, direction: f32) -> f32 {
+    if direction > 0.0 {
+        (origin.floor() + 1.0 - origin) / direction
+    } else if direction < 0.0 {
+        (origin - origin.floor()) / -direction
+    } else {
+        f32::INFINITY
     }
 }
 
-fn chunk_to_world(chunk_pos: &Vector3<i32>) -> Vector3<f32> {
-    Vector3::<f32>::new(
-        chunk_pos.x as f32 * CHUNK_SIZE as f32,
-        chunk_pos.y as f32 * CHUNK_SIZE as f32,
-        chunk_pos.z as f32 * CHUNK_SIZE as f32,
-    )
+fn draw_mesh<'a>(
+    render_pass: &mut wgpu::RenderPass<'a>, buffers: &MeshBuffers, bind_group: Option<generational_arena::Index>,
+    camera_bind_group: &'a wgpu::BindGroup, arena: &'a MeshArena
+) -> Result<()> {
+    let (Some(v_buf_index), Some(i_buf_index), Some(bind_group_index))
+        = (buffers.vertex_buffer, buffers.index_buffer, bind_group) else {
+        return Ok(());
+    };
+
+    let vertex_buffer = arena.buffer.get(v_buf_index).context("no v_buf_index")?;
+    let index_buffer = arena.buffer.get(i_buf_index).context("no i_buf_index")?;
+    let chunk_bind_group = arena.bind_group.get(bind_group_index).context("no bind_group_index")?;
+    let _ = draw_chunk(
+        render_pass,
+        buffers.index_count,
+        camera_bind_group,
+        chunk_bind_group,
+        vertex_buffer,
+        index_buffer
+    );
+    Ok(())
 }
 
 fn draw_chunk<'a, 'b>(
-    render_pass: &mut wgpu::RenderPass<'a>, i_count: u32, camera_bind_group: &'a wgpu::BindGroup, v_buf: &'a wgpu::Buffer, i_buf: &'a wgpu::Buffer
+    render_pass: &mut wgpu::RenderPass<'a>, i_count: u32, camera_bind_group: &'a wgpu::BindGroup,
+    chunk_bind_group: &'a wgpu::BindGroup, v_buf: &'a wgpu::Buffer, i_buf: &'a wgpu::Buffer
 ) -> anyhow::Result<()> {
 
     render_pass.set_vertex_buffer(0, v_buf.slice(..));
     render_pass.set_index_buffer(i_buf.slice(..), wgpu::IndexFormat::Uint32);
     render_pass.set_bind_group(0, &camera_bind_group, &[]);
+    render_pass.set_bind_group(1, chunk_bind_group, &[]);
     render_pass.draw_indexed(0..i_count, 0, 0..1);
 
     Ok(())
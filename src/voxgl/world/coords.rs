@@ -0,0 +1,139 @@
+use std::ops::{Add, Deref, Sub};
+use cgmath::Vector3;
+
+use super::chunk::ChunkDims;
+
+// Chunk coordinates: one unit per chunk.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct ChunkPos(pub Vector3<i32>);
+
+// Voxel coordinates inside a chunk, each axis in 0..ChunkDims::extent().
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct LocalPos(pub Vector3<i32>);
+
+// Absolute voxel coordinates.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct WorldPos(pub Vector3<i32>);
+
+impl ChunkPos {
+    // chunk containing a point in world space, flooring towards negative infinity
+    pub fn containing(pos: Vector3<f32>) -> Self {
+        WorldPos::containing(pos).chunk()
+    }
+
+    pub fn origin(&self) -> WorldPos {
+        let extent = ChunkDims::get().extent();
+        WorldPos(Vector3::new(self.x * extent.x, self.y * extent.y, self.z * extent.z))
+    }
+
+    pub fn world_origin(&self) -> Vector3<f32> {
+        self.origin().0.map(|c| c as f32)
+    }
+}
+
+impl LocalPos {
+    pub fn new(x: i32, y: i32, z: i32) -> Self {
+        Self(Vector3::new(x, y, z))
+    }
+
+    pub fn is_valid(&self) -> bool {
+        let extent = ChunkDims::get().extent();
+        (0..extent.x).contains(&self.x) && (0..extent.y).contains(&self.y) && (0..extent.z).contains(&self.z)
+    }
+}
+
+impl WorldPos {
+    // voxel containing a point in world space, flooring towards negative infinity
+    pub fn containing(pos: Vector3<f32>) -> Self {
+        Self(pos.map(|c| c.floor() as i32))
+    }
+
+    pub fn chunk(&self) -> ChunkPos {
+        let extent = ChunkDims::get().extent();
+        ChunkPos(Vector3::new(self.x.div_euclid(extent.x), self.y.div_euclid(extent.y), self.z.div_euclid(extent.z)))
+    }
+
+    pub fn local(&self) -> LocalPos {
+        let extent = ChunkDims::get().extent();
+        LocalPos(Vector3::new(self.x.rem_euclid(extent.x), self.y.rem_euclid(extent.y), self.z.rem_euclid(extent.z)))
+    }
+
+    pub fn split(&self) -> (ChunkPos, LocalPos) {
+        (self.chunk(), self.local())
+    }
+}
+
+macro_rules! impl_vector_ops {
+    ($($pos:ty),*) => {$(
+        impl Deref for $pos {
+            type Target = Vector3<i32>;
+
+            fn deref(&self) -> &Vector3<i32> {
+                &self.0
+            }
+        }
+
+        impl Add<Vector3<i32>> for $pos {
+            type Output = Self;
+
+            fn add(self, offset: Vector3<i32>) -> Self {
+                Self(self.0 + offset)
+            }
+        }
+
+        impl Sub<Vector3<i32>> for $pos {
+            type Output = Self;
+
+            fn sub(self, offset: Vector3<i32>) -> Self {
+                Self(self.0 - offset)
+            }
+        }
+    )*};
+}
+
+impl_vector_ops!(ChunkPos, LocalPos, WorldPos);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // splits `pos` on every axis and checks it joins back into the same position
+    fn assert_round_trip(pos: Vector3<i32>, chunk: Vector3<i32>, local: Vector3<i32>) {
+        let (chunk_pos, local_pos) = WorldPos(pos).split();
+        assert_eq!(chunk_pos.0, chunk, "chunk of {:?}", pos);
+        assert_eq!(local_pos.0, local, "local position of {:?}", pos);
+        assert!(local_pos.is_valid());
+        assert_eq!(chunk_pos.origin() + local_pos.0, WorldPos(pos));
+    }
+
+    #[test]
+    fn negative_positions_round_trip() {
+        let extent = ChunkDims::get().extent();
+        let last = extent - Vector3::new(1, 1, 1);
+        let minus_one = Vector3::new(-1, -1, -1);
+
+        assert_round_trip(minus_one, minus_one, last);
+        assert_round_trip(-extent, minus_one, Vector3::new(0, 0, 0));
+        assert_round_trip(-extent - Vector3::new(1, 1, 1), Vector3::new(-2, -2, -2), last);
+    }
+
+    #[test]
+    fn positive_positions_round_trip() {
+        let extent = ChunkDims::get().extent();
+        assert_round_trip(Vector3::new(0, 0, 0), Vector3::new(0, 0, 0), Vector3::new(0, 0, 0));
+        assert_round_trip(extent, Vector3::new(1, 1, 1), Vector3::new(0, 0, 0));
+    }
+
+    #[test]
+    fn fractional_negative_points_floor() {
+        let extent = ChunkDims::get().extent().map(|c| c as f32);
+
+        assert_eq!(WorldPos::containing(Vector3::new(-0.5, -0.01, -0.99)).0, Vector3::new(-1, -1, -1));
+        assert_eq!(WorldPos::containing(Vector3::new(-1.5, -2.0, 0.5)).0, Vector3::new(-2, -2, 0));
+        assert_eq!(ChunkPos::containing(Vector3::new(-0.5, -0.5, -0.5)).0, Vector3::new(-1, -1, -1));
+
+        // just inside the chunk's far side, then just past it
+        assert_eq!(ChunkPos::containing(-extent + Vector3::new(0.25, 0.25, 0.25)).0, Vector3::new(-1, -1, -1));
+        assert_eq!(ChunkPos::containing(-extent - Vector3::new(0.25, 0.25, 0.25)).0, Vector3::new(-2, -2, -2));
+    }
+}
@@ -0,0 +1,233 @@
+use std::collections::HashMap;
+use anyhow::{Context, Result};
+use cgmath::Vector3;
+use rand::{rngs::StdRng, Rng, SeedableRng};
+
+use super::{
+    biome::Biome,
+    chunk::{ChunkData, ChunkDims},
+    coords::{ChunkPos, LocalPos, WorldPos},
+    registry::BlockRegistry,
+    terrain::{ChunkColumns, Terrain},
+    voxel::{BlockState, Voxel, VoxelId},
+};
+
+// Vein of `block` replacing stone, `veins` times per chunk below `max_y`.
+struct OreRule {
+    block: &'static str,
+    veins: u32,
+    length: u32,
+    max_y: i32,
+}
+
+enum Feature {
+    // single block on the ground
+    Plant(&'static str),
+    // stack of up to n blocks
+    Column(&'static str, i32),
+    // blob of up to the given radius, half buried in the ground; at most `structures::MAX_REACH`
+    Boulder(&'static str, i32),
+}
+
+struct BiomeRules {
+    ores: &'static [OreRule],
+    // tried in order for each surface column, each with its own chance
+    features: &'static [(Feature, f64)],
+}
+
+const COMMON_ORES: &[OreRule] = &[
+    OreRule { block: "coal_ore", veins: 6, length: 8, max_y: 64 },
+    OreRule { block: "iron_ore", veins: 3, length: 5, max_y: 16 },
+];
+
+const MOUNTAIN_ORES: &[OreRule] = &[
+    OreRule { block: "coal_ore", veins: 8, length: 10, max_y: 96 },
+    OreRule { block: "iron_ore", veins: 6, length: 6, max_y: 48 },
+];
+
+fn rules(biome: Biome) -> BiomeRules {
+    match biome {
+        Biome::Desert => BiomeRules {
+            ores: COMMON_ORES,
+            features: &[(Feature::Column("cactus", 3), 0.005), (Feature::Plant("dead_bush"), 0.01)],
+        },
+        Biome::Plains => BiomeRules {
+            ores: COMMON_ORES,
+            features: &[
+                (Feature::Boulder("cobblestone", 2), 0.001),
+                (Feature::Plant("flower_red"), 0.03),
+                (Feature::Plant("flower_yellow"), 0.03),
+            ],
+        },
+        Biome::Mountains => BiomeRules {
+            ores: MOUNTAIN_ORES,
+            features: &[(Feature::Boulder("cobblestone", 3), 0.008)],
+        },
+        Biome::Tundra => BiomeRules {
+            ores: COMMON_ORES,
+            features: &[(Feature::Boulder("cobblestone", 2), 0.003), (Feature::Plant("dead_bush"), 0.005)],
+        },
+        Biome::Beach => BiomeRules {
+            ores: COMMON_ORES,
+            features: &[],
+        },
+    }
+}
+
+// Places ores and small surface features into freshly generated chunks. Everything is drawn
+// from a random generator seeded by the world seed and the chunk position, so a chunk always
+// decorates the same way no matter when or in which order it is generated. Features rooted near
+// the border may reach into neighbours; those voxels are routed like structure writes.
+pub struct Decorator {
+    seed: u64,
+    stone: VoxelId,
+    blocks: HashMap<&'static str, VoxelId>,
+}
+
+impl Decorator {
+    pub fn new(seed: u64) -> Result<Self> {
+        let registry = BlockRegistry::get();
+        let block = |name: &str| registry.id(name).with_context(|| format!("decorator needs block {name:?}"));
+
+        let mut blocks = HashMap::new();
+        for biome in Biome::ALL {
+            let rules = rules(biome);
+            let features = rules.features.iter().map(|(feature, _)| match feature {
+                Feature::Plant(name) | Feature::Column(name, _) | Feature::Boulder(name, _) => *name,
+            });
+            for name in rules.ores.iter().map(|ore| ore.block).chain(features) {
+                blocks.insert(name, block(name)?);
+            }
+        }
+
+        Ok(Self { seed, stone: block("stone")?, blocks })
+    }
+
+    // returns the feature voxels that lie in neighbouring chunks
+    pub fn decorate(
+        &self, chunk: &mut ChunkData, chunk_pos: &ChunkPos, terrain: &Terrain, columns: &ChunkColumns
+    ) -> Vec<(WorldPos, Voxel)> {
+        let dims = ChunkDims::get();
+        let mut rng = StdRng::seed_from_u64(self.chunk_seed(chunk_pos));
+
+        let center_biome = columns.get(dims.size / 2, dims.size / 2).biome;
+        for ore in rules(center_biome).ores {
+            self.place_ore(chunk, chunk_pos, ore, &mut rng);
+        }
+
+        let mut features = FeatureWrites { chunk, origin: chunk_pos.origin(), outside: Vec::new() };
+        for x in 0..dims.size {
+            for z in 0..dims.size {
+                let chunk = &*features.chunk;
+                let biome = columns.get(x, z).biome;
+                let Some(ground) = surface(chunk, chunk_pos, terrain, columns, x, z).filter(|ground| {
+                    chunk.get_voxel(ground).is_some_and(|v| v.id == terrain.surface_block(biome))
+                }) else {
+                    continue;
+                };
+
+                for (feature, chance) in rules(biome).features {
+                    if rng.gen::<f64>() < *chance {
+                        self.place_feature(&mut features, ground, feature, &mut rng);
+                        break;
+                    }
+                }
+            }
+        }
+        features.outside
+    }
+
+    fn place_ore(&self, chunk: &mut ChunkData, chunk_pos: &ChunkPos, ore: &OreRule, rng: &mut StdRng) {
+        let dims = ChunkDims::get();
+        let id = self.blocks[ore.block];
+        let origin_y = chunk_pos.origin().y;
+
+        for _ in 0..ore.veins {
+            let mut pos = LocalPos::new(rng.gen_range(0..dims.size), rng.gen_range(0..dims.height), rng.gen_range(0..dims.size));
+            if origin_y + pos.y > ore.max_y {
+                continue;
+            }
+
+            // veins wander, so every voxel is checked against the limit, not only the start
+            for _ in 0..ore.length {
+                if origin_y + pos.y <= ore.max_y && chunk.get_voxel(&pos).is_some_and(|v| v.id == self.stone) {
+                    chunk.set_voxel(&pos, Voxel::from_id(id));
+                }
+                let mut step = Vector3::new(0, 0, 0);
+                step[rng.gen_range(0..3)] = if rng.gen() { 1 } else { -1 };
+                pos = pos + step;
+            }
+        }
+    }
+
+    fn place_feature(&self, features: &mut FeatureWrites, ground: LocalPos, feature: &Feature, rng: &mut StdRng) {
+        let ground = ground.0;
+        // plants come fully grown
+        let grown = |name: &str| {
+            Voxel::from_id(self.blocks[name]).with_state(BlockState::default().with_growth(BlockState::MAX_GROWTH))
+        };
+        match feature {
+            Feature::Plant(name) => features.place_in_air(ground + Vector3::unit_y(), grown(name)),
+            Feature::Column(name, max_height) => {
+                for dy in 1..=rng.gen_range(1..=*max_height) {
+                    features.place_in_air(ground + Vector3::new(0, dy, 0), grown(name));
+                }
+            }
+            Feature::Boulder(name, max_radius) => {
+                let radius = rng.gen_range(1..=*max_radius);
+                for dx in -radius..=radius {
+                    for dy in -radius..=radius {
+                        for dz in -radius..=radius {
+                            if dx * dx + dy * dy + dz * dz <= radius * radius {
+                                features.place_in_air(ground + Vector3::new(dx, dy, dz), Voxel::from_id(self.blocks[name]));
+                            }
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    fn chunk_seed(&self, chunk_pos: &ChunkPos) -> u64 {
+        self.seed
+            ^ (chunk_pos.x as u64).wrapping_mul(0x9E37_79B9_7F4A_7C15)
+            ^ (chunk_pos.y as u64).wrapping_mul(0xC2B2_AE3D_27D4_EB4F)
+            ^ (chunk_pos.z as u64).wrapping_mul(0x1656_67B1_9E37_79F9)
+    }
+}
+
+// topmost solid voxel of the column with air above it; above the chunk's top voxel the
+// terrain tells whether the chunk there starts with air
+pub fn surface(
+    chunk: &ChunkData, chunk_pos: &ChunkPos, terrain: &Terrain, columns: &ChunkColumns, x: i32, z: i32
+) -> Option<LocalPos> {
+    let height = ChunkDims::get().height;
+    let origin = chunk_pos.origin();
+    let air_above = |y: i32| match chunk.get_voxel(&LocalPos::new(x, y + 1, z)) {
+        Some(voxel) => voxel.id == VoxelId::EMPTY,
+        None => terrain.is_air(columns.get(x, z), origin.x + x, origin.y + y + 1, origin.z + z),
+    };
+    (0..height).rev()
+        .map(|y| LocalPos::new(x, y, z))
+        .find(|pos| chunk.get_voxel(pos).is_some_and(|v| v.is_solid()) && air_above(pos.y))
+}
+
+// Chunk being decorated, and the feature voxels that fell outside it.
+struct FeatureWrites<'a> {
+    chunk: &'a mut ChunkData,
+    origin: WorldPos,
+    outside: Vec<(WorldPos, Voxel)>,
+}
+
+impl FeatureWrites<'_> {
+    // features never replace existing blocks; voxels past the chunk border are kept for the
+    // neighbour, which applies the same rule once they are routed there
+    fn place_in_air(&mut self, local: Vector3<i32>, voxel: Voxel) {
+        let pos = LocalPos(local);
+        if !pos.is_valid() {
+            self.outside.push((self.origin + local, voxel));
+        } else if self.chunk.get_voxel(&pos).is_some_and(|v| v.id == VoxelId::EMPTY) {
+            self.chunk.set_voxel(&pos, voxel);
+        }
+    }
+}
@@ -0,0 +1,91 @@
+use opensimplex_noise_rs::OpenSimplexNoise;
+
+// vertical distance over which the density falls from solid to air around the biome height
+const DENSITY_SQUASH: f64 = 12.0;
+// strength of the 3D noise added on top; together with the squash it bounds how far
+// overhangs and arches reach above and below the biome height
+const OVERHANG_STRENGTH: f64 = 1.0;
+const OVERHANG_SCALE: f64 = 0.03;
+
+// large open caverns where low frequency noise is high
+const CHEESE_SCALE: f64 = 0.02;
+const CHEESE_THRESHOLD: f64 = 0.5;
+// winding tunnels where two noise fields are both close to zero
+const WORM_SCALE: f64 = 0.025;
+const WORM_RADIUS: f64 = 0.06;
+// caves stay this far under the biome height so they only break the surface occasionally
+const CAVE_CEILING: i32 = 4;
+
+// What the density field leaves at a voxel.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Density {
+    Solid,
+    // air above or around the terrain
+    Open,
+    // air carved out of solid rock
+    Cave,
+}
+
+// 3D density terrain: solid wherever the density is positive, with caves carved out afterwards.
+pub struct DensityField {
+    overhangs: OpenSimplexNoise,
+    cheese: OpenSimplexNoise,
+    worm_a: OpenSimplexNoise,
+    worm_b: OpenSimplexNoise,
+}
+
+impl DensityField {
+    pub fn new(seed: u64) -> Self {
+        let noise = |offset: u64| OpenSimplexNoise::new(Some(seed.wrapping_add(offset) as i64));
+        Self {
+            overhangs: noise(10),
+            cheese: noise(11),
+            worm_a: noise(12),
+            worm_b: noise(13),
+        }
+    }
+
+    // how far above or below `height` solid voxels can still be found
+    pub fn reach() -> i32 {
+        (OVERHANG_STRENGTH * DENSITY_SQUASH).ceil() as i32
+    }
+
+    pub fn is_solid(&self, x: i32, y: i32, z: i32, height: i32) -> bool {
+        self.sample(x, y, z, height) == Density::Solid
+    }
+
+    pub fn sample(&self, x: i32, y: i32, z: i32, height: i32) -> Density {
+        let reach = Self::reach();
+        if y >= height + reach {
+            return Density::Open;
+        }
+        if y >= height - reach {
+            let (fx, fy, fz) = (x as f64, y as f64, z as f64);
+            let density = (height - y) as f64 / DENSITY_SQUASH
+                + self.overhangs.eval_3d(fx * OVERHANG_SCALE, fy * OVERHANG_SCALE, fz * OVERHANG_SCALE) * OVERHANG_STRENGTH;
+            if density <= 0.0 {
+                return Density::Open;
+            }
+        }
+
+        if self.is_cave(x, y, z, height) {
+            Density::Cave
+        } else {
+            Density::Solid
+        }
+    }
+
+    fn is_cave(&self, x: i32, y: i32, z: i32, height: i32) -> bool {
+        if y > height - CAVE_CEILING {
+            return false;
+        }
+
+        let (fx, fy, fz) = (x as f64, y as f64, z as f64);
+        if self.cheese.eval_3d(fx * CHEESE_SCALE, fy * CHEESE_SCALE * 2.0, fz * CHEESE_SCALE) > CHEESE_THRESHOLD {
+            return true;
+        }
+
+        let (wx, wy, wz) = (fx * WORM_SCALE, fy * WORM_SCALE, fz * WORM_SCALE);
+        self.worm_a.eval_3d(wx, wy, wz).abs() < WORM_RADIUS && self.worm_b.eval_3d(wx, wy, wz).abs() < WORM_RADIUS
+    }
+}
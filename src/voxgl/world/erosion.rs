@@ -0,0 +1,350 @@
+use std::{
+    cmp::Ordering,
+    collections::{BinaryHeap, HashMap},
+    sync::{Arc, Mutex, OnceLock},
+};
+use rand::{rngs::StdRng, Rng, SeedableRng};
+
+use super::terrain_config::ErosionConfig;
+
+// eroded regions kept around; the least recently used one is dropped past this
+const MAX_CACHED_REGIONS: usize = 64;
+
+// Hydraulic erosion and river carving over the noise heightmap.
+//
+// The world is split into square regions of `region_size` columns. Each region simulates an
+// area twice its size centred on it, so every column is covered by the four nearest regions.
+// Their results are blended with tent weights that fall to zero at the edge of each simulated
+// area, which keeps the eroded terrain continuous across regions. A region only depends on the
+// seed and its coordinates, so the result does not depend on the order chunks are generated in.
+pub struct Erosion {
+    seed: u64,
+    sea_level: f32,
+    config: ErosionConfig,
+    regions: Mutex<RegionCache>,
+}
+
+// Regions by coordinates, each with the tick it was last used on. A slot is inserted before its
+// region is simulated, so workers asking for the same region wait for one simulation.
+#[derive(Default)]
+struct RegionCache {
+    slots: HashMap<(i32, i32), (RegionSlot, u64)>,
+    tick: u64,
+}
+
+type RegionSlot = Arc<OnceLock<Region>>;
+
+// Eroded height and water surface of one column; `water` equals `height` on dry land.
+pub struct ErodedColumn {
+    pub height: f64,
+    pub water: f64,
+}
+
+// Simulated area of one region, `2 * region_size` columns on each side.
+struct Region {
+    heights: Vec<f32>,
+    water: Vec<f32>,
+}
+
+// Simulation grid, row-major in z.
+struct Grid {
+    size: usize,
+    heights: Vec<f32>,
+}
+
+// Cell waiting in the priority flood, lowest priority first.
+struct FloodCell {
+    priority: f32,
+    index: usize,
+}
+
+// Result of a priority flood: the level each cell fills up to, the neighbour it drains into and
+// the order cells left the flood in.
+struct Flood {
+    level: Vec<f32>,
+    receiver: Vec<usize>,
+    order: Vec<usize>,
+}
+
+impl Erosion {
+    pub fn new(seed: u64, sea_level: i32, config: &ErosionConfig) -> Self {
+        Self {
+            seed,
+            sea_level: sea_level as f32,
+            config: config.clone(),
+            regions: Mutex::new(RegionCache::default()),
+        }
+    }
+
+    // eroded column at (x, z); `base` gives the uneroded height of any column
+    pub fn column(&self, x: i32, z: i32, base: &dyn Fn(i32, i32) -> f64) -> ErodedColumn {
+        let region_size = self.config.region_size as f64;
+        let axis = |c: i32| {
+            let u = (c as f64 - region_size / 2.0) / region_size;
+            (u.floor() as i32, u - u.floor())
+        };
+        let (rx, tx) = axis(x);
+        let (rz, tz) = axis(z);
+
+        let mut column = ErodedColumn { height: 0.0, water: 0.0 };
+        for (dx, wx) in [(0, 1.0 - tx), (1, tx)] {
+            for (dz, wz) in [(0, 1.0 - tz), (1, tz)] {
+                let weight = wx * wz;
+                if weight <= 0.0 {
+                    continue;
+                }
+
+                let slot = self.region_slot(rx + dx, rz + dz);
+                let region = slot.get_or_init(|| self.simulate(rx + dx, rz + dz, base));
+                let (ox, oz) = self.region_origin(rx + dx, rz + dz);
+                let index = (z - oz) as usize * self.area_size() + (x - ox) as usize;
+                column.height += region.heights[index] as f64 * weight;
+                column.water += region.water[index] as f64 * weight;
+            }
+        }
+        column
+    }
+
+    fn area_size(&self) -> usize {
+        self.config.region_size as usize * 2
+    }
+
+    // world column of the first cell in a region's simulated area
+    fn region_origin(&self, rx: i32, rz: i32) -> (i32, i32) {
+        let size = self.config.region_size;
+        (rx * size - size / 2, rz * size - size / 2)
+    }
+
+    // cached region, simulated by whoever initializes it first; the lock is only held for the lookup
+    fn region_slot(&self, rx: i32, rz: i32) -> RegionSlot {
+        let mut cache = self.regions.lock().unwrap();
+        cache.tick += 1;
+        let tick = cache.tick;
+
+        if let Some((slot, last_used)) = cache.slots.get_mut(&(rx, rz)) {
+            *last_used = tick;
+            return slot.clone();
+        }
+
+        if cache.slots.len() >= MAX_CACHED_REGIONS {
+            let oldest = cache.slots.iter().min_by_key(|(_, (_, last_used))| *last_used).map(|(key, _)| *key);
+            if let Some(oldest) = oldest {
+                cache.slots.remove(&oldest);
+            }
+        }
+        let slot = Arc::new(OnceLock::new());
+        cache.slots.insert((rx, rz), (slot.clone(), tick));
+        slot
+    }
+
+    fn simulate(&self, rx: i32, rz: i32, base: &dyn Fn(i32, i32) -> f64) -> Region {
+        let size = self.area_size();
+        let (ox, oz) = self.region_origin(rx, rz);
+        let mut grid = Grid {
+            size,
+            heights: (0..size * size)
+                .map(|index| base(ox + (index % size) as i32, oz + (index / size) as i32) as f32)
+                .collect(),
+        };
+
+        let mut rng = StdRng::seed_from_u64(self.region_seed(rx, rz));
+        let droplets = (size * size) as f64 * self.config.droplets;
+        for _ in 0..droplets as usize {
+            let start = (rng.gen_range(0.0..(size - 1) as f32), rng.gen_range(0.0..(size - 1) as f32));
+            self.run_droplet(&mut grid, start);
+        }
+
+        let jitter = (0..size * size).map(|_| rng.gen_range(0.0..=self.config.meander)).collect::<Vec<_>>();
+        let water = self.carve_rivers(&mut grid, &jitter);
+        Region { heights: grid.heights, water }
+    }
+
+    fn region_seed(&self, rx: i32, rz: i32) -> u64 {
+        self.seed.rotate_left(31)
+            ^ (rx as u64).wrapping_mul(0x9FB2_1C65_1E98_DF25)
+            ^ (rz as u64).wrapping_mul(0xC13F_A9A9_02A6_328F)
+    }
+
+    // one water droplet running downhill, eroding where it speeds up and depositing where it slows
+    fn run_droplet(&self, grid: &mut Grid, start: (f32, f32)) {
+        let config = &self.config;
+        let (mut x, mut z) = start;
+        let (mut dir_x, mut dir_z) = (0.0f32, 0.0f32);
+        let (mut speed, mut water, mut sediment) = (1.0f32, 1.0f32, 0.0f32);
+
+        for _ in 0..config.lifetime {
+            let (height, grad_x, grad_z) = grid.sample(x, z);
+            // the sea swallows the droplet and whatever it carries
+            if height < self.sea_level {
+                break;
+            }
+
+            dir_x = dir_x * config.inertia - grad_x * (1.0 - config.inertia);
+            dir_z = dir_z * config.inertia - grad_z * (1.0 - config.inertia);
+            let length = (dir_x * dir_x + dir_z * dir_z).sqrt();
+            if length <= f32::EPSILON {
+                break;
+            }
+            dir_x /= length;
+            dir_z /= length;
+
+            let (old_x, old_z) = (x, z);
+            x += dir_x;
+            z += dir_z;
+            if !grid.contains(x, z) {
+                break;
+            }
+
+            let delta = grid.sample(x, z).0 - height;
+            let capacity = (-delta * speed * water * config.capacity).max(config.min_capacity);
+
+            if delta > 0.0 || sediment > capacity {
+                // fill the pit it climbs out of, or drop what it can no longer carry
+                let amount = if delta > 0.0 { delta.min(sediment) } else { (sediment - capacity) * config.deposit_speed };
+                sediment -= amount;
+                grid.deposit(old_x, old_z, amount);
+            } else {
+                // never dig deeper than the step down, which would leave pits behind
+                let amount = ((capacity - sediment) * config.erode_speed).min(-delta);
+                sediment += amount;
+                grid.deposit(old_x, old_z, -amount);
+            }
+
+            speed = (speed * speed + delta * config.gravity).max(0.0).sqrt();
+            water *= 1.0 - config.evaporation;
+        }
+    }
+
+    // Routes water across the region from the sea and the area border: depressions fill up into
+    // lakes, and every other cell drains into a neighbour. Cells that collect enough upstream
+    // water are carved into river beds. Returns the water surface of each cell.
+    fn carve_rivers(&self, grid: &mut Grid, jitter: &[f32]) -> Vec<f32> {
+        let size = grid.size;
+        let mut water = self.flood(grid, &vec![0.0; size * size]).level;
+
+        // jittering the order water spreads in lets rivers meander instead of running straight
+        // down gentle slopes; lake levels come from the exact flood above
+        let routes = self.flood(grid, jitter);
+
+        // cells leave the flood after everything they drain into, so walking it backwards
+        // passes each cell's flow on before its receiver is visited
+        let mut flow = vec![1u32; size * size];
+        for &index in routes.order.iter().rev() {
+            if routes.receiver[index] != usize::MAX {
+                flow[routes.receiver[index]] += flow[index];
+            }
+        }
+
+        let config = &self.config;
+        for index in 0..size * size {
+            let height = grid.heights[index];
+            // lakes keep their flooded level, dry land has its surface as water level
+            if water[index] > height || height < self.sea_level || flow[index] < config.river_threshold {
+                continue;
+            }
+
+            let depth = (2.0 + (flow[index] as f32 / config.river_threshold as f32).ln()).min(config.river_depth);
+            grid.heights[index] = height - depth;
+            water[index] = height - 1.0;
+        }
+        water
+    }
+
+    // priority flood seeded from the sea and the area border, spreading to the lowest cell first
+    fn flood(&self, grid: &Grid, jitter: &[f32]) -> Flood {
+        let size = grid.size;
+        let mut level = grid.heights.clone();
+        let mut receiver = vec![usize::MAX; size * size];
+        let mut visited = vec![false; size * size];
+        let mut order = Vec::with_capacity(size * size);
+        let mut queue = BinaryHeap::new();
+
+        for index in 0..size * size {
+            let (x, z) = (index % size, index / size);
+            let border = x == 0 || z == 0 || x == size - 1 || z == size - 1;
+            if border || grid.heights[index] < self.sea_level {
+                visited[index] = true;
+                queue.push(FloodCell { priority: level[index] + jitter[index], index });
+            }
+        }
+
+        while let Some(FloodCell { index, .. }) = queue.pop() {
+            order.push(index);
+            for next in grid.neighbours(index) {
+                if visited[next] {
+                    continue;
+                }
+                visited[next] = true;
+                receiver[next] = index;
+                level[next] = level[next].max(level[index]);
+                queue.push(FloodCell { priority: level[next] + jitter[next], index: next });
+            }
+        }
+        Flood { level, receiver, order }
+    }
+}
+
+impl Grid {
+    fn contains(&self, x: f32, z: f32) -> bool {
+        x >= 0.0 && z >= 0.0 && x < (self.size - 1) as f32 && z < (self.size - 1) as f32
+    }
+
+    // bilinear height and gradient at a point inside the grid
+    fn sample(&self, x: f32, z: f32) -> (f32, f32, f32) {
+        let (cx, cz) = (x.floor() as usize, z.floor() as usize);
+        let (u, v) = (x - cx as f32, z - cz as f32);
+        let index = cz * self.size + cx;
+
+        let h00 = self.heights[index];
+        let h10 = self.heights[index + 1];
+        let h01 = self.heights[index + self.size];
+        let h11 = self.heights[index + self.size + 1];
+
+        let height = h00 * (1.0 - u) * (1.0 - v) + h10 * u * (1.0 - v) + h01 * (1.0 - u) * v + h11 * u * v;
+        let grad_x = (h10 - h00) * (1.0 - v) + (h11 - h01) * v;
+        let grad_z = (h01 - h00) * (1.0 - u) + (h11 - h10) * u;
+        (height, grad_x, grad_z)
+    }
+
+    // spreads a height change over the four cells around a point
+    fn deposit(&mut self, x: f32, z: f32, amount: f32) {
+        let (cx, cz) = (x.floor() as usize, z.floor() as usize);
+        let (u, v) = (x - cx as f32, z - cz as f32);
+        let index = cz * self.size + cx;
+
+        self.heights[index] += amount * (1.0 - u) * (1.0 - v);
+        self.heights[index + 1] += amount * u * (1.0 - v);
+        self.heights[index + self.size] += amount * (1.0 - u) * v;
+        self.heights[index + self.size + 1] += amount * u * v;
+    }
+
+    fn neighbours(&self, index: usize) -> impl Iterator<Item = usize> {
+        let size = self.size as i32;
+        let (x, z) = ((index % self.size) as i32, (index / self.size) as i32);
+        [(1, 0), (-1, 0), (0, 1), (0, -1)].into_iter()
+            .map(move |(dx, dz)| (x + dx, z + dz))
+            .filter(move |&(x, z)| x >= 0 && z >= 0 && x < size && z < size)
+            .map(move |(x, z)| (z * size + x) as usize)
+    }
+}
+
+impl PartialEq for FloodCell {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for FloodCell {}
+
+impl PartialOrd for FloodCell {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for FloodCell {
+    // reversed so the max-heap pops the lowest priority; ties break on the index to stay deterministic
+    fn cmp(&self, other: &Self) -> Ordering {
+        other.priority.total_cmp(&self.priority).then_with(|| other.index.cmp(&self.index))
+    }
+}
@@ -0,0 +1,205 @@
+use std::str::FromStr;
+use anyhow::{Context, Result};
+use serde::Deserialize;
+
+use crate::voxgl::config::Config;
+
+use super::{
+    chunk::{ChunkData, ChunkDims},
+    coords::{ChunkPos, LocalPos, WorldPos},
+    decorator::Decorator,
+    height_image::HeightImage,
+    registry::BlockRegistry,
+    structures::Structures,
+    terrain::Terrain,
+    terrain_config::TerrainConfig,
+    voxel::{Voxel, VoxelId},
+};
+
+// depth of the checkerboard pattern below y = 0
+const CHECKERBOARD_DEPTH: i32 = 16;
+
+// Fills freshly allocated (empty) chunks with the base terrain. Generators are shared with the
+// generation workers, so they must be usable from any thread.
+pub trait TerrainGenerator: Send + Sync {
+    fn generate(&self, chunk: &mut ChunkData, chunk_pos: &ChunkPos);
+
+    // natural terrain exposes its biomes so decorations and structures can follow them;
+    // synthetic worlds return None and stay undecorated
+    fn terrain(&self) -> Option<&Terrain> {
+        None
+    }
+}
+
+// Everything that shapes a new chunk: the base terrain, then decorations and structures on
+// natural terrain. Read-only, so one instance serves every generation worker.
+pub struct WorldGenerator {
+    base: Box<dyn TerrainGenerator>,
+    decorator: Decorator,
+    structures: Structures,
+}
+
+impl WorldGenerator {
+    pub fn new(config: &Config, terrain: &TerrainConfig) -> Result<Self> {
+        Ok(Self {
+            base: config.terrain.create(config, terrain)?,
+            decorator: Decorator::new(config.seed)?,
+            structures: Structures::new(config.seed)?,
+        })
+    }
+
+    // fills an empty chunk and returns the structure and feature voxels rooted in it that are
+    // still to be placed, which may lie in neighbouring chunks
+    pub fn generate(&self, chunk: &mut ChunkData, chunk_pos: &ChunkPos) -> Vec<(WorldPos, Voxel)> {
+        let Some(terrain) = self.base.terrain() else {
+            self.base.generate(chunk, chunk_pos);
+            return Vec::new();
+        };
+
+        // natural terrain shapes each column once for all three passes
+        let columns = terrain.columns(chunk_pos);
+        terrain.fill(chunk, chunk_pos, &columns);
+        let mut writes = self.decorator.decorate(chunk, chunk_pos, terrain, &columns);
+        writes.extend(self.structures.generate(chunk, chunk_pos, terrain, &columns));
+        writes
+    }
+
+    pub fn terrain(&self) -> Option<&Terrain> {
+        self.base.terrain()
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, Deserialize)]
+pub enum TerrainKind {
+    // noise terrain, one solid column per x/z up to the biome height
+    #[default]
+    Heightmap,
+    // noise terrain shaped by 3D density, with overhangs and caves
+    Density,
+    // heights (and optionally biomes) read from an image
+    Image,
+    // horizontal layers from `superflat_layers`
+    Superflat,
+    // nothing at all
+    Void,
+    // alternating solid and empty voxels, every face visible
+    Checkerboard,
+}
+
+impl TerrainKind {
+    pub fn create(self, config: &Config, terrain: &TerrainConfig) -> Result<Box<dyn TerrainGenerator>> {
+        Ok(match self {
+            Self::Heightmap => Box::new(Terrain::new(config.seed, config.sea_level, false, terrain)?),
+            Self::Density => Box::new(Terrain::new(config.seed, config.sea_level, true, terrain)?),
+            Self::Image => {
+                let image = HeightImage::load(&config.image)?;
+                Box::new(Terrain::new(config.seed, config.sea_level, false, terrain)?.with_image(image))
+            }
+            Self::Superflat => Box::new(Superflat::new(&config.superflat_layers)?),
+            Self::Void => Box::new(Void),
+            Self::Checkerboard => Box::new(Checkerboard::new()?),
+        })
+    }
+}
+
+impl FromStr for TerrainKind {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "heightmap" => Ok(Self::Heightmap),
+            "density" => Ok(Self::Density),
+            "image" => Ok(Self::Image),
+            "superflat" => Ok(Self::Superflat),
+            "void" => Ok(Self::Void),
+            "checkerboard" => Ok(Self::Checkerboard),
+            _ => anyhow::bail!("unknown terrain {s}, expected heightmap, density, image, superflat, void or checkerboard"),
+        }
+    }
+}
+
+// Flat world built from a layer string such as "stone,3*dirt,grass", listed bottom to top
+// starting at y = 0.
+pub struct Superflat {
+    layers: Vec<VoxelId>,
+}
+
+impl Superflat {
+    pub fn new(layers: &str) -> Result<Self> {
+        let registry = BlockRegistry::get();
+        let mut ids = Vec::new();
+
+        for layer in layers.split(',').map(str::trim).filter(|layer| !layer.is_empty()) {
+            let (count, name) = match layer.split_once('*') {
+                Some((count, name)) => {
+                    let count = count.trim().parse::<usize>().with_context(|| format!("invalid layer count in {layer:?}"))?;
+                    (count, name.trim())
+                }
+                None => (1, layer),
+            };
+            let id = registry.id(name).with_context(|| format!("unknown block {name:?} in superflat layers"))?;
+            ids.extend(std::iter::repeat_n(id, count));
+        }
+
+        Ok(Self { layers: ids })
+    }
+}
+
+impl TerrainGenerator for Superflat {
+    fn generate(&self, chunk: &mut ChunkData, chunk_pos: &ChunkPos) {
+        let dims = ChunkDims::get();
+        let origin = chunk_pos.origin();
+
+        for y in 0..dims.height {
+            let Some(id) = usize::try_from(origin.y + y).ok().and_then(|layer| self.layers.get(layer)) else {
+                continue;
+            };
+            for x in 0..dims.size {
+                for z in 0..dims.size {
+                    chunk.set_voxel(&LocalPos::new(x, y, z), Voxel::from_id(*id));
+                }
+            }
+        }
+        chunk.voxels.compact();
+    }
+}
+
+pub struct Void;
+
+impl TerrainGenerator for Void {
+    fn generate(&self, _chunk: &mut ChunkData, _chunk_pos: &ChunkPos) {}
+}
+
+pub struct Checkerboard {
+    blocks: [VoxelId; 2],
+}
+
+impl Checkerboard {
+    pub fn new() -> Result<Self> {
+        let registry = BlockRegistry::get();
+        let block = |name: &str| registry.id(name).with_context(|| format!("checkerboard needs block {name:?}"));
+        Ok(Self { blocks: [block("stone")?, block("snow")?] })
+    }
+}
+
+impl TerrainGenerator for Checkerboard {
+    fn generate(&self, chunk: &mut ChunkData, chunk_pos: &ChunkPos) {
+        let dims = ChunkDims::get();
+        let origin = chunk_pos.origin();
+
+        for x in 0..dims.size {
+            for y in 0..dims.height {
+                for z in 0..dims.size {
+                    let pos = LocalPos::new(x, y, z);
+                    let world = origin + pos.0;
+                    if !(-CHECKERBOARD_DEPTH..0).contains(&world.y) || (world.x + world.y + world.z).rem_euclid(2) != 0 {
+                        continue;
+                    }
+                    // alternate the block per layer so neighbouring layers are easy to tell apart
+                    chunk.set_voxel(&pos, Voxel::from_id(self.blocks[world.y.rem_euclid(2) as usize]));
+                }
+            }
+        }
+        chunk.voxels.compact();
+    }
+}
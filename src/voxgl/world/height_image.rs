@@ -0,0 +1,139 @@
+use std::{fs, path::Path};
+use anyhow::{Context, Result};
+use serde::Deserialize;
+
+use super::biome::Biome;
+
+// What happens to columns past the edge of the image.
+#[derive(Debug, Clone, Copy, Default, Deserialize)]
+pub enum EdgeMode {
+    // repeat the border pixels forever
+    #[default]
+    Clamp,
+    // repeat the whole image
+    Tile,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct HeightImageConfig {
+    // grayscale PNG (8 or 16 bit) or raw little-endian 16-bit square heightmap (.r16 / .raw)
+    pub path: String,
+    // optional PNG of the same size, each pixel colored like the biome it belongs to
+    pub biome_map: Option<String>,
+    // world columns per pixel
+    pub horizontal_scale: f64,
+    // height in voxels of a white pixel above a black one
+    pub vertical_scale: f64,
+    // height of a black pixel
+    pub base_height: f64,
+    pub edges: EdgeMode,
+}
+
+impl Default for HeightImageConfig {
+    fn default() -> Self {
+        Self {
+            path: "resources/heightmap.png".to_string(),
+            biome_map: None,
+            horizontal_scale: 1.0,
+            vertical_scale: 64.0,
+            base_height: 0.0,
+            edges: EdgeMode::default(),
+        }
+    }
+}
+
+// Heightmap image centred on the world origin, sampled into column heights.
+pub struct HeightImage {
+    width: usize,
+    depth: usize,
+    // pixel values in 0..1, row-major in z
+    heights: Vec<f32>,
+    biomes: Option<Vec<Biome>>,
+    config: HeightImageConfig,
+}
+
+impl HeightImage {
+    pub fn load(config: &HeightImageConfig) -> Result<Self> {
+        if config.horizontal_scale <= 0.0 {
+            anyhow::bail!("horizontal_scale must be positive");
+        }
+
+        let path = Path::new(&config.path);
+        let (width, depth, heights) = read_heights(path).with_context(|| format!("invalid heightmap {:?}", path))?;
+        log::info!("heightmap {:?}: {}x{} pixels", path, width, depth);
+
+        let biomes = match &config.biome_map {
+            Some(biome_path) => {
+                let map = image::open(biome_path).with_context(|| format!("invalid biome map {:?}", biome_path))?.into_rgb8();
+                if (map.width() as usize, map.height() as usize) != (width, depth) {
+                    anyhow::bail!("biome map {:?} is {}x{}, the heightmap is {}x{}", biome_path, map.width(), map.height(), width, depth);
+                }
+                Some(map.pixels().map(|pixel| Biome::from_map_color(pixel.0)).collect())
+            }
+            None => None,
+        };
+
+        Ok(Self { width, depth, heights, biomes, config: config.clone() })
+    }
+
+    // column height at (x, z), interpolated between pixels when they span several columns
+    pub fn height(&self, x: i32, z: i32) -> f64 {
+        let (px, pz) = self.pixel_coords(x, z);
+        let (x0, z0) = (px.floor(), pz.floor());
+        let (u, v) = ((px - x0) as f32, (pz - z0) as f32);
+        let (x0, z0) = (x0 as i64, z0 as i64);
+
+        let h00 = self.heights[self.index(x0, z0)];
+        let h10 = self.heights[self.index(x0 + 1, z0)];
+        let h01 = self.heights[self.index(x0, z0 + 1)];
+        let h11 = self.heights[self.index(x0 + 1, z0 + 1)];
+        let value = h00 * (1.0 - u) * (1.0 - v) + h10 * u * (1.0 - v) + h01 * (1.0 - u) * v + h11 * u * v;
+
+        self.config.base_height + value as f64 * self.config.vertical_scale
+    }
+
+    // biome painted on the biome map at (x, z), if there is one
+    pub fn biome(&self, x: i32, z: i32) -> Option<Biome> {
+        let biomes = self.biomes.as_ref()?;
+        let (px, pz) = self.pixel_coords(x, z);
+        Some(biomes[self.index(px.round() as i64, pz.round() as i64)])
+    }
+
+    // position of a column in pixels, with the image centred on the origin
+    fn pixel_coords(&self, x: i32, z: i32) -> (f64, f64) {
+        let scale = self.config.horizontal_scale;
+        (x as f64 / scale + self.width as f64 / 2.0, z as f64 / scale + self.depth as f64 / 2.0)
+    }
+
+    fn index(&self, px: i64, pz: i64) -> usize {
+        let (width, depth) = (self.width as i64, self.depth as i64);
+        let (px, pz) = match self.config.edges {
+            EdgeMode::Clamp => (px.clamp(0, width - 1), pz.clamp(0, depth - 1)),
+            EdgeMode::Tile => (px.rem_euclid(width), pz.rem_euclid(depth)),
+        };
+        (pz * width + px) as usize
+    }
+}
+
+// (width, depth, normalized heights) of a PNG or raw 16-bit heightmap
+fn read_heights(path: &Path) -> Result<(usize, usize, Vec<f32>)> {
+    let raw = path.extension().is_some_and(|ext| ext.eq_ignore_ascii_case("r16") || ext.eq_ignore_ascii_case("raw"));
+    if !raw {
+        // 8-bit images are widened, so both depths end up in 0..=u16::MAX
+        let image = image::open(path)?.into_luma16();
+        let heights = image.pixels().map(|pixel| pixel.0[0] as f32 / u16::MAX as f32).collect();
+        return Ok((image.width() as usize, image.height() as usize, heights));
+    }
+
+    // raw dumps carry no header, so they have to be square
+    let bytes = fs::read(path)?;
+    let side = ((bytes.len() / 2) as f64).sqrt() as usize;
+    if side == 0 || side * side * 2 != bytes.len() {
+        anyhow::bail!("raw heightmap of {} bytes is not a square of 16-bit samples", bytes.len());
+    }
+    let heights = bytes.chunks_exact(2)
+        .map(|sample| u16::from_le_bytes([sample[0], sample[1]]) as f32 / u16::MAX as f32)
+        .collect();
+    Ok((side, side, heights))
+}
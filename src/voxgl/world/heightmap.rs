@@ -0,0 +1,83 @@
+use std::collections::{BTreeMap, HashMap};
+
+use super::{chunk::{ChunkData, ChunkDims}, coords::{ChunkPos, LocalPos}};
+
+const NO_TOP: i16 = -1;
+
+// Local y of the highest solid voxel in each x/z column of one chunk.
+pub struct Heightmap {
+    tops: Vec<i16>,
+}
+
+impl Heightmap {
+    pub fn from_chunk(chunk: &ChunkData) -> Self {
+        let dims = ChunkDims::get();
+        let columns = (dims.size * dims.size) as usize;
+
+        if let Some(voxel) = chunk.voxels.uniform() {
+            let top = if voxel.is_solid() { (dims.height - 1) as i16 } else { NO_TOP };
+            return Self { tops: vec![top; columns] };
+        }
+
+        let mut heightmap = Self { tops: vec![NO_TOP; columns] };
+        for x in 0..dims.size {
+            for z in 0..dims.size {
+                let top = (0..dims.height).rev()
+                    .find(|y| chunk.get_voxel(&LocalPos::new(x, *y, z)).is_some_and(|v| v.is_solid()));
+                heightmap.set_top(x, z, top);
+            }
+        }
+        heightmap
+    }
+
+    pub fn top(&self, x: i32, z: i32) -> Option<i32> {
+        let top = self.tops[Self::column_index(x, z)];
+        (top != NO_TOP).then_some(top as i32)
+    }
+
+    pub fn set_top(&mut self, x: i32, z: i32, top: Option<i32>) {
+        self.tops[Self::column_index(x, z)] = top.map_or(NO_TOP, |y| y as i16);
+    }
+
+    fn column_index(x: i32, z: i32) -> usize {
+        (x * ChunkDims::get().size + z) as usize
+    }
+}
+
+// Heightmaps of every loaded chunk, grouped by chunk column and ordered bottom to top.
+pub struct Heightmaps {
+    columns: HashMap<(i32, i32), BTreeMap<i32, Heightmap>>,
+}
+
+impl Heightmaps {
+    pub fn new() -> Self {
+        Self { columns: HashMap::new() }
+    }
+
+    pub fn insert(&mut self, chunk_pos: &ChunkPos, heightmap: Heightmap) {
+        self.columns.entry((chunk_pos.x, chunk_pos.z)).or_default().insert(chunk_pos.y, heightmap);
+    }
+
+    pub fn remove(&mut self, chunk_pos: &ChunkPos) {
+        let key = (chunk_pos.x, chunk_pos.z);
+        if let Some(column) = self.columns.get_mut(&key) {
+            column.remove(&chunk_pos.y);
+            if column.is_empty() {
+                self.columns.remove(&key);
+            }
+        }
+    }
+
+    pub fn get_mut(&mut self, chunk_pos: &ChunkPos) -> Option<&mut Heightmap> {
+        self.columns.get_mut(&(chunk_pos.x, chunk_pos.z))?.get_mut(&chunk_pos.y)
+    }
+
+    // loaded chunks of the column at or below `chunk_pos`, topmost first
+    pub fn column_below(&self, chunk_pos: &ChunkPos) -> impl Iterator<Item = (i32, &Heightmap)> {
+        let top = chunk_pos.y;
+        self.columns.get(&(chunk_pos.x, chunk_pos.z))
+            .into_iter()
+            .flat_map(move |column| column.range(..=top).rev())
+            .map(|(y, heightmap)| (*y, heightmap))
+    }
+}
@@ -1,34 +1,159 @@
+use std::str::FromStr;
 use anyhow::Context;
 use cgmath::Vector3;
+use serde::Deserialize;
 use wgpu::util::DeviceExt;
 
 use crate::voxgl::{
     rendering::{arena::MeshArena, vertex::Vertex},
     world::{
-        chunk::CHUNK_SIZE,
+        chunk::{ChunkDims, ChunkMesh, MeshBuffers, TransparentFaces},
         chunks::Chunks,
+        coords::{ChunkPos, WorldPos},
         quad::{Face, Quad},
-        voxel::Voxel,
+        registry::BlockRegistry,
+        voxel::{Voxel, VoxelId},
     }
 };
 
-pub fn build_chunk_mesh(
-    chunks: &mut Chunks, chunk_pos: &Vector3<i32>, chunk_world_pos: &Vector3<f32>, device: &wgpu::Device, arena: &mut MeshArena
-) -> bool {
+#[derive(Debug, Clone, Copy, Default, Deserialize)]
+pub enum MesherKind {
+    // one quad per visible voxel face
+    Naive,
+    // neighbouring faces of the same block and color merged into larger quads
+    #[default]
+    Greedy,
+}
+
+impl FromStr for MesherKind {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> anyhow::Result<Self> {
+        match s {
+            "naive" => Ok(Self::Naive),
+            "greedy" => Ok(Self::Greedy),
+            _ => anyhow::bail!("unknown mesher {s}, expected naive or greedy"),
+        }
+    }
+}
+
+// Voxels the meshers read, by world position; None where nothing is loaded.
+pub trait VoxelSource {
+    fn voxel(&self, world_pos: &WorldPos) -> Option<Voxel>;
+}
+
+impl VoxelSource for Chunks {
+    fn voxel(&self, world_pos: &WorldPos) -> Option<Voxel> {
+        self.try_get_voxel(world_pos).ok().copied()
+    }
+}
+
+// Copy of a chunk and the one voxel thick border around it, which is everything meshing the
+// chunk reads, so it can be meshed on another thread.
+pub struct ChunkSnapshot {
+    // world position of the lowest border voxel
+    min: WorldPos,
+    extent: Vector3<i32>,
+    voxels: Vec<Option<Voxel>>,
+}
+
+impl ChunkSnapshot {
+    pub fn capture(source: &impl VoxelSource, chunk_pos: &ChunkPos) -> Self {
+        let min = chunk_pos.origin() - Vector3::new(1, 1, 1);
+        let extent = ChunkDims::get().extent() + Vector3::new(2, 2, 2);
+        let mut voxels = Vec::with_capacity((extent.x * extent.y * extent.z) as usize);
+
+        for x in 0..extent.x {
+            for y in 0..extent.y {
+                for z in 0..extent.z {
+                    voxels.push(source.voxel(&(min + Vector3::new(x, y, z))));
+                }
+            }
+        }
+        Self { min, extent, voxels }
+    }
+}
 
-    let chunk_size = CHUNK_SIZE as i32;
-    let mut quads = Vec::<Quad>::new();
+impl VoxelSource for ChunkSnapshot {
+    fn voxel(&self, world_pos: &WorldPos) -> Option<Voxel> {
+        let local = world_pos.0 - self.min.0;
+        if (0..3).any(|axis| local[axis] < 0 || local[axis] >= self.extent[axis]) {
+            return None;
+        }
+        self.voxels[((local.x * self.extent.y + local.y) * self.extent.z + local.z) as usize]
+    }
+}
 
-    for x in 0..chunk_size {
-        for y in 0..chunk_size {
-            for z in 0..chunk_size {
+// Vertices and indices of one mesh, built off the render thread.
+#[derive(Default)]
+pub struct MeshGeometry {
+    vertices: Vec<Vertex>,
+    indices: Vec<u32>,
+}
+
+// Both meshes of a chunk, waiting to be uploaded.
+pub struct ChunkMeshData {
+    pub opaque: MeshGeometry,
+    pub transparent: MeshGeometry,
+    // world centre of each transparent quad, used to sort them back to front
+    pub transparent_centers: Vec<Vector3<f32>>,
+}
+
+pub fn build_mesh_data(source: &impl VoxelSource, chunk_pos: &ChunkPos, mesher: MesherKind) -> ChunkMeshData {
+    let quads = match mesher {
+        MesherKind::Naive => naive_quads(source, chunk_pos),
+        MesherKind::Greedy => greedy_quads(source, chunk_pos),
+    };
 
-                let voxel_local_pos = Vector3::<f32>::new(x as f32, y as f32, z as f32);
-                let voxel_world_pos = chunk_world_pos + voxel_local_pos;
+    let origin = chunk_pos.world_origin();
+    let geometry = |quads: &Vec<Quad>| {
+        let mut geometry = MeshGeometry::default();
+        process_quads(quads, origin, &mut geometry.vertices, &mut geometry.indices);
+        geometry
+    };
+    let transparent_centers = quads.transparent.iter()
+        .map(|quad| quad.vertices.iter().sum::<Vector3<f32>>() / 4.0)
+        .collect();
+    ChunkMeshData { opaque: geometry(&quads.opaque), transparent: geometry(&quads.transparent), transparent_centers }
+}
+
+// creates the GPU buffers of a chunk mesh, with transparent faces sorted for a camera at `eye`;
+// must run on the render thread
+pub fn upload_chunk_mesh(
+    chunk: &mut ChunkMesh, chunk_pos: &ChunkPos, mut data: ChunkMeshData, eye: Vector3<f32>, device: &wgpu::Device, arena: &mut MeshArena
+) {
+    if data.opaque.indices.is_empty() && data.transparent.indices.is_empty() {
+        log::warn!("trying to load empty quads at {:?}", chunk_pos.world_origin());
+    }
+
+    if chunk.bind_group.is_none() {
+        let (origin_buffer, bind_group) = arena.insert_chunk_bindings(chunk_pos, device);
+        chunk.origin_buffer = Some(origin_buffer);
+        chunk.bind_group = Some(bind_group);
+    }
+
+    upload_geometry(data.opaque, &mut chunk.opaque, device, arena);
+    chunk.transparent_faces = TransparentFaces::new(&data.transparent_centers, &data.transparent.indices);
+    if !data.transparent.indices.is_empty() {
+        data.transparent.indices = chunk.transparent_faces.sorted_indices(eye);
+        upload_geometry(data.transparent, &mut chunk.transparent, device, arena);
+    }
+}
+
+fn naive_quads(source: &impl VoxelSource, chunk_pos: &ChunkPos) -> ChunkQuads {
+    let dims = ChunkDims::get();
+    let mut quads = ChunkQuads::default();
+
+    for x in 0..dims.size {
+        for y in 0..dims.height {
+            for z in 0..dims.size {
+
+                let voxel_world_pos = chunk_pos.origin() + Vector3::new(x, y, z);
                 if let Ok(
                     (voxel, back, left, bottom)
-                ) = adjacent_voxels(chunks, Vector3::new(x, y, z), chunk_pos) {
+                ) = adjacent_voxels(source, voxel_world_pos) {
                     process_voxel(
+                        source,
                         &voxel,
                         voxel_world_pos,
                         &left,
@@ -40,101 +165,217 @@ pub fn build_chunk_mesh(
             }
         }
     }
+    quads
+}
 
-    if quads.is_empty() {
-        log::warn!("trying to load empty quads at {:?}", chunk_world_pos);
-    }
-    
-    let mut vertices = Vec::<Vertex>::new();
-    let mut indices = Vec::<u32>::new();
+#[derive(Default)]
+struct ChunkQuads {
+    opaque: Vec<Quad>,
+    transparent: Vec<Quad>,
+}
 
-    process_quads(&quads, &mut vertices, &mut indices);
-    if let Some(chunk) = chunks.get_chunk_mesh_mut(chunk_pos) {
-        let v_count = vertices.len() as u32;
-        let i_count = indices.len() as u32;
+fn upload_geometry(geometry: MeshGeometry, buffers: &mut MeshBuffers, device: &wgpu::Device, arena: &mut MeshArena) {
+    let v_count = geometry.vertices.len() as u32;
+    let i_count = geometry.indices.len() as u32;
 
-        let (v_buf, i_buf) = construct_buffers(vertices, indices, device);
-        let v_buf = arena.buffer.insert(v_buf);
-        let i_buf = arena.buffer.insert(i_buf);
-        chunk.update_mesh_buffers(v_buf, i_buf, v_count, i_count);
-        return i_count != 0;
-    }
-    false
+    let (v_buf, i_buf) = construct_buffers(geometry.vertices, geometry.indices, device);
+    let v_buf = arena.buffer.insert(v_buf);
+    let i_buf = arena.buffer.insert(i_buf);
+    buffers.update_mesh_buffers(v_buf, i_buf, v_count, i_count);
 }
 
-fn adjacent_voxels<'a>(
-    chunks: &'a Chunks, local_pos: Vector3<i32>, chunk_pos: &Vector3<i32>
-) -> anyhow::Result<(&'a Voxel, &'a Voxel, &'a Voxel, &'a Voxel)> {
-
-    let (x, y, z) = (local_pos.x, local_pos.y, local_pos.z);
+fn adjacent_voxels(
+    source: &impl VoxelSource, world_pos: WorldPos
+) -> anyhow::Result<(Voxel, Voxel, Voxel, Voxel)> {
 
-    let voxel = chunks.try_get_voxel(chunk_pos, &Vector3::new(x, y, z)).context("no voxel")?;
-    let back = chunks.try_get_voxel(chunk_pos, &Vector3::new(x, y, z - 1)).context("no back")?;
-    let left = chunks.try_get_voxel(chunk_pos, &Vector3::new(x - 1, y, z)).context("no left")?;
-    let bottom = chunks.try_get_voxel(chunk_pos, &Vector3::new(x, y - 1, z)).context("no bottom")?;
+    let voxel = source.voxel(&world_pos).context("no voxel")?;
+    let back = source.voxel(&(world_pos - Vector3::unit_z())).context("no back")?;
+    let left = source.voxel(&(world_pos - Vector3::unit_x())).context("no left")?;
+    let bottom = source.voxel(&(world_pos - Vector3::unit_y())).context("no bottom")?;
 
     Ok((voxel, back, left, bottom))
 }
 
+// each voxel owns the faces shared with its left, bottom and back neighbours; either side of
+// the shared face is drawn when the voxel on the other side does not occlude it
 fn process_voxel(
-    voxel: &Voxel, voxel_world_pos: Vector3<f32>, left: &Voxel, bottom: &Voxel, back: &Voxel, quads: &mut Vec<Quad>
+    source: &impl VoxelSource, voxel: &Voxel, voxel_world_pos: WorldPos,
+    left: &Voxel, bottom: &Voxel, back: &Voxel, quads: &mut ChunkQuads
 ) {
-    match voxel.is_solid() {
-        true => {
-            // voxel is solid
-            if !left.is_solid() {
-                push_quad(*voxel, Face::Left, voxel_world_pos, quads);
-            }
-            if !bottom.is_solid() {
-                push_quad(*voxel, Face::Bottom, voxel_world_pos, quads);
+    let pos = voxel_world_pos.0.map(|c| c as f32);
+
+    for (neighbour, face, neighbour_face) in [
+        (left, Face::Left, Face::Right),
+        (bottom, Face::Bottom, Face::Top),
+        (back, Face::Back, Face::Front),
+    ] {
+        if face_visible(voxel, neighbour) {
+            let key = FaceKey::new(source, voxel, face, voxel_world_pos);
+            push_quad(key, Quad::from_face(face, pos), quads);
+        }
+        if face_visible(neighbour, voxel) {
+            let key = FaceKey::new(source, neighbour, neighbour_face, voxel_world_pos);
+            push_quad(key, Quad::from_face(neighbour_face, pos), quads);
+        }
+    }
+}
+
+fn face_visible(voxel: &Voxel, neighbour: &Voxel) -> bool {
+    voxel.is_visible() && !neighbour.occludes(voxel)
+}
+
+// Faces with equal keys look the same and can share a quad.
+#[derive(Clone, Copy, PartialEq)]
+struct FaceKey {
+    id: VoxelId,
+    color: u32,
+    occlusion: [u8; 4],
+}
+
+impl FaceKey {
+    // key of a unit face placed like `Quad::from_face` at `pos`
+    fn new(source: &impl VoxelSource, voxel: &Voxel, face: Face, pos: WorldPos) -> Self {
+        let color = BlockRegistry::get().color_index(voxel.id, voxel.state, face);
+        Self { id: voxel.id, color, occlusion: face_occlusion(source, face, pos) }
+    }
+}
+
+// Counts the voxels around each vertex in the layer the face looks into: the two beside the
+// vertex and the one diagonal to it. Two side voxels hide the corner completely.
+fn face_occlusion(source: &impl VoxelSource, face: Face, pos: WorldPos) -> [u8; 4] {
+    let normal = face.get_normal().map(|c| c as i32);
+    let axis = face.axis() as usize;
+    let (u, v) = ((axis + 1) % 3, (axis + 2) % 3);
+    // faces are placed on the low side of `pos`, so only the negative ones look out of it
+    let front = if normal[axis] < 0 { pos + normal } else { pos };
+
+    Quad::from_face(face, pos.0.map(|c| c as f32)).vertices.map(|vertex| {
+        let (mut side_u, mut side_v) = (Vector3::new(0, 0, 0), Vector3::new(0, 0, 0));
+        side_u[u] = if vertex[u] as i32 > pos.0[u] { 1 } else { -1 };
+        side_v[v] = if vertex[v] as i32 > pos.0[v] { 1 } else { -1 };
+
+        let shades = |offset: Vector3<i32>| {
+            source.voxel(&(front + offset)).is_some_and(|voxel| voxel.is_visible() && !voxel.is_transparent())
+        };
+        match (shades(side_u), shades(side_v)) {
+            (true, true) => 3,
+            (a, b) => a as u8 + b as u8 + shades(side_u + side_v) as u8,
+        }
+    })
+}
+
+// Sweeps the chunk one slice at a time along each axis. Faces on a slice plane are marked
+// in a mask, then covered row by row with the largest rectangles of equal faces.
+fn greedy_quads(source: &impl VoxelSource, chunk_pos: &ChunkPos) -> ChunkQuads {
+    let extent = ChunkDims::get().extent();
+    let origin = chunk_pos.origin();
+    let mut quads = ChunkQuads::default();
+
+    // same ownership as the naive mesher: the plane at the low side of each voxel
+    for (face, neighbour_face) in [(Face::Left, Face::Right), (Face::Bottom, Face::Top), (Face::Back, Face::Front)] {
+        let normal = face.axis() as usize;
+        let (u, v) = ((normal + 1) % 3, (normal + 2) % 3);
+        let (width, height) = (extent[u] as usize, extent[v] as usize);
+        let step = -face.get_normal().map(|c| c as i32);
+
+        let mut own = vec![None; width * height];
+        let mut neighbours = vec![None; width * height];
+
+        for slice in 0..extent[normal] {
+            let local = |i: usize, j: usize| {
+                let mut local = Vector3::new(0, 0, 0);
+                local[normal] = slice;
+                local[u] = i as i32;
+                local[v] = j as i32;
+                local
+            };
+
+            for j in 0..height {
+                for i in 0..width {
+                    let world_pos = origin + local(i, j);
+                    let (Some(voxel), Some(neighbour)) = (source.voxel(&world_pos), source.voxel(&(world_pos - step))) else {
+                        continue;
+                    };
+                    if face_visible(&voxel, &neighbour) {
+                        own[j * width + i] = Some(FaceKey::new(source, &voxel, face, world_pos));
+                    }
+                    if face_visible(&neighbour, &voxel) {
+                        neighbours[j * width + i] = Some(FaceKey::new(source, &neighbour, neighbour_face, world_pos));
+                    }
+                }
             }
-            if !back.is_solid() {
-                push_quad(*voxel, Face::Back, voxel_world_pos, quads);
+
+            for (mask, face) in [(&mut own, face), (&mut neighbours, neighbour_face)] {
+                merge_mask(mask, width, height, |i, j, w, h, key| {
+                    let mut size = Vector3::new(1.0, 1.0, 1.0);
+                    size[u] = w as f32;
+                    size[v] = h as f32;
+                    let pos = (origin + local(i, j)).0.map(|c| c as f32);
+                    push_quad(key, Quad::sized(face, pos, size), &mut quads);
+                });
             }
         }
+    }
+    quads
+}
 
-        false => {
-            // voxel is empty
-            if left.is_solid() {
-                push_quad(*left, Face::Right, voxel_world_pos, quads);
+// covers the set cells of a mask with rectangles of equal keys, clearing it on the way
+fn merge_mask(
+    mask: &mut [Option<FaceKey>], width: usize, height: usize, mut emit: impl FnMut(usize, usize, usize, usize, FaceKey)
+) {
+    for j in 0..height {
+        let mut i = 0;
+        while i < width {
+            let Some(key) = mask[j * width + i] else {
+                i += 1;
+                continue;
+            };
+
+            let mut w = 1;
+            while i + w < width && mask[j * width + i + w] == Some(key) {
+                w += 1;
             }
-            if bottom.is_solid() {
-                push_quad(*bottom, Face::Top, voxel_world_pos, quads);
+            let mut h = 1;
+            while j + h < height && mask[(j + h) * width + i..(j + h) * width + i + w].iter().all(|cell| *cell == Some(key)) {
+                h += 1;
             }
-            if back.is_solid() {
-                push_quad(*back, Face::Front, voxel_world_pos, quads);
+
+            for row in j..j + h {
+                mask[row * width + i..row * width + i + w].fill(None);
             }
+            emit(i, j, w, h, key);
+            i += w;
         }
     }
 }
 
-fn push_quad(voxel: Voxel, face: Face, pos: Vector3<f32>, quads: &mut Vec<Quad>) {
-    let mut quad = Quad::from_face(face, pos);
-    quad.color = voxel.id.get_color();
-    quads.push(quad);
-}
+fn push_quad(key: FaceKey, mut quad: Quad, quads: &mut ChunkQuads) {
+    let block = BlockRegistry::get().block(key.id);
+    quad.color = key.color;
+    quad.occlusion = key.occlusion;
+    quad.light = block.light;
 
-fn color_as_array(color: &wgpu::Color) -> [f32; 4] {
-    [color.r as f32, color.g as f32, color.b as f32, color.a as f32]
+    match block.transparent {
+        true => quads.transparent.push(quad),
+        false => quads.opaque.push(quad),
+    }
 }
 
-fn process_quads(quads: &Vec<Quad>, vertices: &mut Vec<Vertex>, indices: &mut Vec<u32>) {
+// vertices are stored relative to the chunk origin, which the shader adds back
+fn process_quads(quads: &Vec<Quad>, origin: Vector3<f32>, vertices: &mut Vec<Vertex>, indices: &mut Vec<u32>) {
     let mut v_index = 0;
     for quad in quads {
         (0..4).for_each(|index| {
-            vertices.push(Vertex {
-                position: quad.vertices[index].into(),
-                normal: quad.face.get_normal().into(),
-                color: color_as_array(&quad.color),
-            });
+            let local_pos = (quad.vertices[index] - origin).map(|c| c as u32);
+            vertices.push(Vertex::new(local_pos, quad.face, quad.occlusion[index], quad.light, quad.color));
         });
 
-        indices.push(v_index);
-        indices.push(v_index + 1);
-        indices.push(v_index + 2);
-        indices.push(v_index);
-        indices.push(v_index + 2);
-        indices.push(v_index + 3);
+        // split along the lighter diagonal, otherwise a dark corner bleeds across the quad
+        let [a, b, c, d] = quad.occlusion.map(|o| o as u32);
+        let first = if a + c > b + d { 1 } else { 0 };
+        for corner in [0, 1, 2, 0, 2, 3] {
+            indices.push(v_index + (first + corner) % 4);
+        }
 
         v_index += 4;
     }
@@ -150,8 +391,154 @@ fn construct_buffers(vertices: Vec<Vertex>, indices: Vec<u32>, device: &wgpu::De
     let index_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
         label: Some("voxel_chunk_indices"),
         contents: bytemuck::cast_slice(&indices),
-        usage: wgpu::BufferUsages::INDEX,
+        // transparent indices are rewritten when they are re-sorted
+        usage: wgpu::BufferUsages::INDEX | wgpu::BufferUsages::COPY_DST,
     });
 
     (vertex_buffer, index_buffer)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::voxgl::world::voxel::{Axis, BlockState};
+
+    // voxels computed from their position, so every neighbouring chunk is "loaded"
+    struct TestWorld<F: Fn(Vector3<i32>) -> Voxel>(F);
+
+    impl<F: Fn(Vector3<i32>) -> Voxel> VoxelSource for TestWorld<F> {
+        fn voxel(&self, world_pos: &WorldPos) -> Option<Voxel> {
+            Some((self.0)(world_pos.0))
+        }
+    }
+
+    fn block(name: &str) -> Voxel {
+        Voxel::from_id(BlockRegistry::get().id(name).unwrap())
+    }
+
+    fn hash(pos: Vector3<i32>) -> u32 {
+        let mut h = (pos.x as u32).wrapping_mul(0x8da6_b343) ^ (pos.y as u32).wrapping_mul(0xd816_3841) ^ (pos.z as u32).wrapping_mul(0xcb1a_b31f);
+        h ^= h >> 15;
+        h = h.wrapping_mul(0x2c1b_3c6d);
+        h ^ (h >> 12)
+    }
+
+    // a mix of empty, opaque, transparent and oriented blocks
+    fn mixed_voxel(hash: u32) -> Voxel {
+        match hash % 9 {
+            0..=2 => Voxel::new(),
+            3 => block("stone"),
+            4 => block("dirt"),
+            5 => block("water"),
+            6 => block("leaves"),
+            7 => block("log").with_state(BlockState::default().with_axis([Axis::X, Axis::Y, Axis::Z][(hash / 9 % 3) as usize])),
+            _ => block("pumpkin").with_state(BlockState::default().with_facing(Face::ALL[(hash / 9 % 6) as usize])),
+        }
+    }
+
+    // (face, cell, color, occlusion, transparent)
+    type UnitFace = (usize, [i32; 3], u32, [u8; 4], bool);
+
+    // every unit face covered by the quads, sorted
+    fn surface(quads: &ChunkQuads) -> Vec<UnitFace> {
+        let mut cells = Vec::new();
+        for (list, transparent) in [(&quads.opaque, false), (&quads.transparent, true)] {
+            for quad in list {
+                let min = quad.vertices.iter().fold(quad.vertices[0], |a, b| Vector3::new(a.x.min(b.x), a.y.min(b.y), a.z.min(b.z)));
+                let max = quad.vertices.iter().fold(quad.vertices[0], |a, b| Vector3::new(a.x.max(b.x), a.y.max(b.y), a.z.max(b.z)));
+                let (min, max) = (min.map(|c| c as i32), max.map(|c| c as i32));
+
+                for x in min.x..max.x.max(min.x + 1) {
+                    for y in min.y..max.y.max(min.y + 1) {
+                        for z in min.z..max.z.max(min.z + 1) {
+                            cells.push((quad.face as usize, [x, y, z], quad.color, quad.occlusion, transparent));
+                        }
+                    }
+                }
+            }
+        }
+        cells.sort();
+        cells
+    }
+
+    fn assert_same_surface(world: &impl VoxelSource, chunk_pos: ChunkPos) -> (ChunkQuads, ChunkQuads) {
+        let naive = naive_quads(world, &chunk_pos);
+        let greedy = greedy_quads(world, &chunk_pos);
+        assert_eq!(surface(&naive), surface(&greedy), "surfaces differ at {:?}", chunk_pos);
+        assert!(greedy.opaque.len() <= naive.opaque.len());
+        assert!(greedy.transparent.len() <= naive.transparent.len());
+        (naive, greedy)
+    }
+
+    #[test]
+    fn random_voxels_have_the_same_surface() {
+        let world = TestWorld(|pos: Vector3<i32>| mixed_voxel(hash(pos)));
+        for chunk_pos in [Vector3::new(0, 0, 0), Vector3::new(-1, 2, 3)] {
+            assert_same_surface(&world, ChunkPos(chunk_pos));
+        }
+    }
+
+    #[test]
+    fn blocky_voxels_have_the_same_surface() {
+        // runs of equal voxels give the greedy mesher something to merge
+        let world = TestWorld(|pos: Vector3<i32>| mixed_voxel(hash(pos.map(|c| c.div_euclid(3)))));
+        for chunk_pos in [Vector3::new(0, 0, 0), Vector3::new(1, -1, -2)] {
+            let (naive, greedy) = assert_same_surface(&world, ChunkPos(chunk_pos));
+            assert!(greedy.opaque.len() < naive.opaque.len());
+        }
+    }
+
+    #[test]
+    fn snapshots_mesh_like_the_world() {
+        let world = TestWorld(|pos: Vector3<i32>| mixed_voxel(hash(pos.map(|c| c.div_euclid(3)))));
+        let chunk_pos = ChunkPos(Vector3::new(1, 0, -1));
+        let snapshot = ChunkSnapshot::capture(&world, &chunk_pos);
+
+        assert_eq!(surface(&naive_quads(&world, &chunk_pos)), surface(&naive_quads(&snapshot, &chunk_pos)));
+        assert_eq!(surface(&greedy_quads(&world, &chunk_pos)), surface(&greedy_quads(&snapshot, &chunk_pos)));
+    }
+
+    #[test]
+    fn terrain_has_the_same_surface() {
+        let world = TestWorld(|pos: Vector3<i32>| {
+            let height = (6.0 + 5.0 * (pos.x as f32 * 0.3).sin() * (pos.z as f32 * 0.2).cos()) as i32;
+            match pos.y {
+                y if y < height - 2 => block("stone"),
+                y if y < height - 1 => block("dirt"),
+                y if y < height => block("grass"),
+                y if y < 5 => block("water"),
+                _ => Voxel::new(),
+            }
+        });
+        for chunk_pos in [Vector3::new(0, 0, 0), Vector3::new(-2, 0, 1)] {
+            assert_same_surface(&world, ChunkPos(chunk_pos));
+        }
+    }
+
+    #[test]
+    fn flat_ground_is_one_quad() {
+        let world = TestWorld(|pos: Vector3<i32>| if pos.y < 4 { block("grass") } else { Voxel::new() });
+        let (naive, greedy) = assert_same_surface(&world, ChunkPos(Vector3::new(0, 0, 0)));
+        let size = ChunkDims::get().size as usize;
+        assert_eq!(naive.opaque.len(), size * size);
+        assert_eq!(greedy.opaque.len(), 1);
+    }
+
+    #[test]
+    fn corners_are_occluded() {
+        // floor at y < 0 with walls at x < 0 and z < 0
+        let world = TestWorld(|pos: Vector3<i32>| if pos.x < 0 || pos.y < 0 || pos.z < 0 { block("stone") } else { Voxel::new() });
+        let (naive, _) = assert_same_surface(&world, ChunkPos(Vector3::new(0, 0, 0)));
+
+        let occlusion_at = |face: Face, vertex: Vector3<f32>| {
+            naive.opaque.iter()
+                .filter(|quad| quad.face == face)
+                .find_map(|quad| quad.vertices.iter().position(|v| *v == vertex).map(|index| quad.occlusion[index]))
+        };
+        assert_eq!(occlusion_at(Face::Top, Vector3::new(0.0, 0.0, 0.0)), Some(3));
+        assert_eq!(occlusion_at(Face::Top, Vector3::new(0.0, 0.0, 5.0)), Some(2));
+        assert_eq!(occlusion_at(Face::Top, Vector3::new(5.0, 0.0, 5.0)), Some(0));
+        assert_eq!(occlusion_at(Face::Right, Vector3::new(0.0, 0.0, 5.0)), Some(2));
+        assert_eq!(occlusion_at(Face::Right, Vector3::new(0.0, 5.0, 5.0)), Some(0));
+    }
+}
@@ -1,6 +1,22 @@
 pub mod quad;
 pub mod chunk;
+pub mod coords;
+pub mod palette;
+pub mod storage;
+pub mod octree;
+pub mod heightmap;
 pub mod voxel;
+pub mod registry;
 pub mod mesh_builder;
 pub mod chunks;
+pub mod query;
 pub mod terrain;
+pub mod terrain_config;
+pub mod generator;
+pub mod workers;
+pub mod biome;
+pub mod density;
+pub mod erosion;
+pub mod height_image;
+pub mod decorator;
+pub mod structures;
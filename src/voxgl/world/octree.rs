@@ -0,0 +1,229 @@
+use std::collections::HashMap;
+use cgmath::Vector3;
+
+use super::{
+    chunk::{ChunkData, ChunkDims},
+    coords::{ChunkPos, LocalPos},
+    storage::VoxelStorage,
+    voxel::Voxel,
+};
+
+// Sparse voxel octree covering one chunk. Any cube of identical voxels collapses into a single
+// leaf, so air above the terrain and solid rock below it cost almost nothing. The cube edge is
+// the larger chunk edge; cells outside the chunk are never read.
+pub struct Octree {
+    root: Node,
+    depth: u32,
+}
+
+enum Node {
+    Leaf(Voxel),
+    Branch(Box<[Node; 8]>),
+}
+
+impl Octree {
+    pub fn from_chunk(chunk: &ChunkData) -> Self {
+        let dims = ChunkDims::get();
+        let depth = dims.size.max(dims.height).trailing_zeros();
+
+        let root = match chunk.voxels.uniform() {
+            Some(voxel) => Node::Leaf(*voxel),
+            None => build(chunk, Vector3::new(0, 0, 0), depth),
+        };
+        Self { root, depth }
+    }
+
+    pub fn get(&self, pos: &LocalPos) -> &Voxel {
+        let mut node = &self.root;
+        let mut level = self.depth;
+        loop {
+            match node {
+                Node::Leaf(voxel) => return voxel,
+                Node::Branch(children) => {
+                    level -= 1;
+                    node = &children[child_index(pos, level)];
+                }
+            }
+        }
+    }
+
+    pub fn set(&mut self, pos: &LocalPos, voxel: Voxel) {
+        set_node(&mut self.root, pos, self.depth, voxel);
+    }
+}
+
+fn build(chunk: &ChunkData, origin: Vector3<i32>, level: u32) -> Node {
+    if level == 0 {
+        return Node::Leaf(chunk.get_voxel(&LocalPos(origin)).copied().unwrap_or_else(Voxel::new));
+    }
+
+    let half = 1 << (level - 1);
+    let children = std::array::from_fn(|index| build(chunk, origin + child_offset(index) * half, level - 1));
+    match uniform_leaf(&children) {
+        Some(voxel) => Node::Leaf(voxel),
+        None => Node::Branch(Box::new(children)),
+    }
+}
+
+fn set_node(node: &mut Node, pos: &LocalPos, level: u32, voxel: Voxel) {
+    if level == 0 {
+        *node = Node::Leaf(voxel);
+        return;
+    }
+
+    if let Node::Leaf(current) = *node {
+        if current == voxel {
+            return;
+        }
+        *node = Node::Branch(Box::new(std::array::from_fn(|_| Node::Leaf(current))));
+    }
+
+    if let Node::Branch(children) = node {
+        set_node(&mut children[child_index(pos, level - 1)], pos, level - 1, voxel);
+        if let Some(merged) = uniform_leaf(children) {
+            *node = Node::Leaf(merged);
+        }
+    }
+}
+
+fn uniform_leaf(children: &[Node; 8]) -> Option<Voxel> {
+    let Node::Leaf(first) = children[0] else {
+        return None;
+    };
+    children.iter()
+        .all(|child| matches!(child, Node::Leaf(voxel) if *voxel == first))
+        .then_some(first)
+}
+
+fn child_index(pos: &LocalPos, level: u32) -> usize {
+    (((pos.x >> level) & 1) | (((pos.y >> level) & 1) << 1) | (((pos.z >> level) & 1) << 2)) as usize
+}
+
+fn child_offset(index: usize) -> Vector3<i32> {
+    let index = index as i32;
+    Vector3::new(index & 1, (index >> 1) & 1, (index >> 2) & 1)
+}
+
+pub struct OctreeStorage {
+    chunks: HashMap<ChunkPos, Octree>,
+}
+
+impl OctreeStorage {
+    pub fn new() -> Self {
+        Self { chunks: HashMap::new() }
+    }
+}
+
+impl VoxelStorage for OctreeStorage {
+    fn contains(&self, chunk_pos: &ChunkPos) -> bool {
+        self.chunks.contains_key(chunk_pos)
+    }
+
+    fn chunk_count(&self) -> usize {
+        self.chunks.len()
+    }
+
+    fn get_voxel(&self, chunk_pos: &ChunkPos, local_pos: &LocalPos) -> Option<&Voxel> {
+        if !local_pos.is_valid() {
+            return None;
+        }
+        self.chunks.get(chunk_pos).map(|octree| octree.get(local_pos))
+    }
+
+    fn set_voxel(&mut self, chunk_pos: &ChunkPos, local_pos: &LocalPos, voxel: Voxel) -> bool {
+        let Some(octree) = self.chunks.get_mut(chunk_pos) else {
+            return false;
+        };
+        if local_pos.is_valid() {
+            octree.set(local_pos, voxel);
+        }
+        true
+    }
+
+    // the generated chunk is only a template here, so it goes straight back to the caller
+    fn insert(&mut self, chunk_pos: ChunkPos, chunk: ChunkData) -> Option<ChunkData> {
+        self.chunks.insert(chunk_pos, Octree::from_chunk(&chunk));
+        Some(chunk)
+    }
+
+    fn remove(&mut self, chunk_pos: &ChunkPos) -> Option<ChunkData> {
+        self.chunks.remove(chunk_pos);
+        None
+    }
+
+    fn drain(&mut self) -> Vec<ChunkData> {
+        self.chunks.clear();
+        Vec::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::{rngs::StdRng, Rng, SeedableRng};
+
+    use super::*;
+    use crate::voxgl::world::{
+        generator::TerrainGenerator,
+        storage::DenseStorage,
+        terrain::{Terrain, DEFAULT_SEA_LEVEL},
+        terrain_config::TerrainConfig,
+        voxel::VoxelId,
+    };
+
+    // every position of the octree's cube and one past it, so cells outside the chunk are read too
+    fn assert_same_voxels(octree: &OctreeStorage, dense: &DenseStorage, chunk_pos: &ChunkPos) {
+        let dims = ChunkDims::get();
+        let edge = dims.size.max(dims.height);
+        for x in -1..=edge {
+            for y in -1..=edge {
+                for z in -1..=edge {
+                    let pos = LocalPos::new(x, y, z);
+                    assert_eq!(
+                        octree.get_voxel(chunk_pos, &pos), dense.get_voxel(chunk_pos, &pos),
+                        "voxel {:?} of chunk {:?}", pos.0, chunk_pos.0
+                    );
+                }
+            }
+        }
+    }
+
+    fn assert_matches_dense() {
+        let terrain = Terrain::new(7, DEFAULT_SEA_LEVEL, true, &TerrainConfig::default()).unwrap();
+        let dims = ChunkDims::get();
+        let mut rng = StdRng::seed_from_u64(5);
+
+        for chunk_pos in [Vector3::new(0, 0, 0), Vector3::new(1, -1, 2), Vector3::new(-2, 3, -1)].map(ChunkPos) {
+            let mut chunk = ChunkData::new();
+            terrain.generate(&mut chunk, &chunk_pos);
+
+            let mut octree = OctreeStorage::new();
+            let mut dense = DenseStorage::new();
+            let template = octree.insert(chunk_pos, chunk).unwrap();
+            dense.insert(chunk_pos, template);
+            assert_same_voxels(&octree, &dense, &chunk_pos);
+
+            // edits split and merge nodes, including writes outside the chunk that both ignore
+            for _ in 0..2000 {
+                let pos = LocalPos::new(
+                    rng.gen_range(0..=dims.size), rng.gen_range(0..=dims.height), rng.gen_range(0..=dims.size)
+                );
+                let voxel = Voxel::from_id(VoxelId(rng.gen_range(0..3)));
+                assert!(octree.set_voxel(&chunk_pos, &pos, voxel));
+                assert!(dense.set_voxel(&chunk_pos, &pos, voxel));
+            }
+            assert_same_voxels(&octree, &dense, &chunk_pos);
+        }
+    }
+
+    #[test]
+    fn cubic_chunks_match_dense_storage() {
+        assert_matches_dense();
+    }
+
+    #[test]
+    fn non_cubic_chunks_match_dense_storage() {
+        for (size, height) in [(16, 64), (32, 8), (4, 16)] {
+            ChunkDims::with(ChunkDims::new(size, height).unwrap(), assert_matches_dense);
+        }
+    }
+}
@@ -0,0 +1,228 @@
+use super::voxel::Voxel;
+
+const WORD_BITS: usize = u64::BITS as usize;
+
+// Voxel storage for a single chunk. Chunks made of one voxel type (all air above the terrain,
+// solid stone below it) keep just that voxel; everything else stores a palette of the distinct
+// voxels plus one bit-packed palette index per voxel.
+pub struct PalettedVoxels {
+    len: usize,
+    storage: Storage,
+}
+
+enum Storage {
+    Uniform(Voxel),
+    Packed {
+        palette: Vec<Voxel>,
+        bits: usize,
+        words: Vec<u64>,
+    },
+}
+
+impl PalettedVoxels {
+    pub fn new(len: usize, voxel: Voxel) -> Self {
+        Self { len, storage: Storage::Uniform(voxel) }
+    }
+
+    pub fn get(&self, index: usize) -> Option<&Voxel> {
+        if index >= self.len {
+            return None;
+        }
+
+        match &self.storage {
+            Storage::Uniform(voxel) => Some(voxel),
+            Storage::Packed { palette, bits, words } => palette.get(read_entry(words, *bits, index)),
+        }
+    }
+
+    // the voxel filling the whole chunk, if there is only one
+    pub fn uniform(&self) -> Option<&Voxel> {
+        match &self.storage {
+            Storage::Uniform(voxel) => Some(voxel),
+            Storage::Packed { .. } => None,
+        }
+    }
+
+    pub fn set(&mut self, index: usize, voxel: Voxel) {
+        if index >= self.len {
+            return;
+        }
+
+        if let Storage::Uniform(current) = self.storage {
+            if current == voxel {
+                return;
+            }
+            self.storage = Storage::Packed {
+                palette: vec![current],
+                bits: 1,
+                words: vec![0; words_for(self.len, 1)],
+            };
+        }
+
+        let Storage::Packed { palette, bits, words } = &mut self.storage else {
+            unreachable!()
+        };
+
+        let entry = match palette.iter().position(|v| *v == voxel) {
+            Some(entry) => entry,
+            None => {
+                palette.push(voxel);
+                if palette.len() > 1 << *bits {
+                    *words = repack(words, *bits, *bits + 1, self.len);
+                    *bits += 1;
+                }
+                palette.len() - 1
+            }
+        };
+        write_entry(words, *bits, index, entry);
+    }
+
+    pub fn fill(&mut self, voxel: Voxel) {
+        self.storage = Storage::Uniform(voxel);
+    }
+
+    // drops palette entries that are no longer referenced and collapses single-voxel chunks
+    pub fn compact(&mut self) {
+        let len = self.len;
+        let Storage::Packed { palette, bits, words } = &mut self.storage else {
+            return;
+        };
+
+        let mut used = vec![false; palette.len()];
+        for index in 0..len {
+            used[read_entry(words, *bits, index)] = true;
+        }
+
+        let used_count = used.iter().filter(|u| **u).count();
+        if used_count == 1 {
+            let entry = used.iter().position(|u| *u).unwrap_or(0);
+            self.storage = Storage::Uniform(palette[entry]);
+            return;
+        }
+        if used_count == palette.len() {
+            return;
+        }
+
+        let mut remap = vec![0; palette.len()];
+        let mut new_palette = Vec::with_capacity(used_count);
+        for (entry, voxel) in palette.iter().enumerate() {
+            if used[entry] {
+                remap[entry] = new_palette.len();
+                new_palette.push(*voxel);
+            }
+        }
+
+        let new_bits = bits_for(new_palette.len());
+        let mut new_words = vec![0; words_for(len, new_bits)];
+        for index in 0..len {
+            write_entry(&mut new_words, new_bits, index, remap[read_entry(words, *bits, index)]);
+        }
+
+        *palette = new_palette;
+        *bits = new_bits;
+        *words = new_words;
+    }
+}
+
+fn bits_for(palette_len: usize) -> usize {
+    (usize::BITS - (palette_len.max(2) - 1).leading_zeros()) as usize
+}
+
+fn words_for(len: usize, bits: usize) -> usize {
+    let per_word = WORD_BITS / bits;
+    len.div_ceil(per_word)
+}
+
+fn read_entry(words: &[u64], bits: usize, index: usize) -> usize {
+    let per_word = WORD_BITS / bits;
+    let shift = (index % per_word) * bits;
+    ((words[index / per_word] >> shift) & ((1 << bits) - 1)) as usize
+}
+
+fn write_entry(words: &mut [u64], bits: usize, index: usize, entry: usize) {
+    let per_word = WORD_BITS / bits;
+    let shift = (index % per_word) * bits;
+    let mask = ((1u64 << bits) - 1) << shift;
+    let word = &mut words[index / per_word];
+    *word = (*word & !mask) | ((entry as u64) << shift);
+}
+
+fn repack(words: &[u64], bits: usize, new_bits: usize, len: usize) -> Vec<u64> {
+    let mut new_words = vec![0; words_for(len, new_bits)];
+    for index in 0..len {
+        write_entry(&mut new_words, new_bits, index, read_entry(words, bits, index));
+    }
+    new_words
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::{rngs::StdRng, Rng, SeedableRng};
+
+    use super::*;
+    use crate::voxgl::world::voxel::VoxelId;
+
+    const LEN: usize = 4096;
+
+    fn bits(voxels: &PalettedVoxels) -> Option<usize> {
+        match &voxels.storage {
+            Storage::Uniform(_) => None,
+            Storage::Packed { bits, .. } => Some(*bits),
+        }
+    }
+
+    fn assert_matches(voxels: &PalettedVoxels, expected: &[Voxel]) {
+        for (index, voxel) in expected.iter().enumerate() {
+            assert_eq!(voxels.get(index), Some(voxel), "voxel {index}");
+        }
+        assert_eq!(voxels.get(expected.len()), None);
+    }
+
+    #[test]
+    fn random_writes_round_trip() {
+        let mut rng = StdRng::seed_from_u64(3);
+        let mut voxels = PalettedVoxels::new(LEN, Voxel::new());
+        let mut expected = vec![Voxel::new(); LEN];
+
+        // each round draws from more distinct voxels, growing the entries past 2, 3 and then 5 bits
+        for (kinds, bits_after) in [(4, 2), (8, 3), (20, 5)] {
+            for _ in 0..LEN {
+                let index = rng.gen_range(0..LEN);
+                let voxel = Voxel::from_id(VoxelId(rng.gen_range(0..kinds)));
+                voxels.set(index, voxel);
+                expected[index] = voxel;
+            }
+            assert_eq!(bits(&voxels), Some(bits_after));
+            assert_matches(&voxels, &expected);
+        }
+
+        // overwrite everything with two voxels, so compacting can drop most of the palette
+        for (index, voxel) in expected.iter_mut().enumerate() {
+            *voxel = Voxel::from_id(VoxelId((index % 3 == 0) as u16));
+            voxels.set(index, *voxel);
+        }
+        voxels.compact();
+        assert_eq!(bits(&voxels), Some(1));
+        assert_matches(&voxels, &expected);
+
+        voxels.fill(Voxel::from_id(VoxelId(7)));
+        expected.fill(Voxel::from_id(VoxelId(7)));
+        voxels.compact();
+        assert_eq!(voxels.uniform(), Some(&expected[0]));
+        assert_matches(&voxels, &expected);
+    }
+
+    #[test]
+    fn compact_collapses_single_voxel_chunks() {
+        let mut voxels = PalettedVoxels::new(LEN, Voxel::new());
+        let stone = Voxel::from_id(VoxelId(1));
+        for index in 0..LEN {
+            voxels.set(index, stone);
+        }
+        assert_eq!(voxels.uniform(), None);
+
+        voxels.compact();
+        assert_eq!(voxels.uniform(), Some(&stone));
+        assert_matches(&voxels, &vec![stone; LEN]);
+    }
+}
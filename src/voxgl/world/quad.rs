@@ -1,11 +1,27 @@
 use cgmath::Vector3;
 
-#[derive(Debug, Clone, Copy)]
+use super::voxel::Axis;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum Face {
     Right, Left, Top, Bottom, Front, Back
 }
 
 impl Face {
+    pub const ALL: [Face; 6] = [Face::Right, Face::Left, Face::Top, Face::Bottom, Face::Front, Face::Back];
+
+    pub fn axis(&self) -> Axis {
+        match self {
+            Self::Right | Self::Left => Axis::X,
+            Self::Top | Self::Bottom => Axis::Y,
+            Self::Front | Self::Back => Axis::Z,
+        }
+    }
+
+    pub fn from_normal(normal: Vector3<i32>) -> Option<Face> {
+        Self::ALL.into_iter().find(|face| face.get_normal().map(|c| c as i32) == normal)
+    }
+
     pub fn get_normal(&self) -> Vector3<f32> {
         match self {
             Self::Right  =>  Vector3::<f32>::unit_x(),
@@ -22,52 +38,62 @@ impl Face {
 pub struct Quad {
     pub vertices: [Vector3<f32>; 4],
     pub face: Face,
-    pub color: wgpu::Color,
+    // entry of the block color table
+    pub color: u32,
+    // occluding voxels around each vertex, 0 (open) to 3 (inner corner)
+    pub occlusion: [u8; 4],
+    // light emitted by the block the face belongs to
+    pub light: u8,
 }
 
 impl Quad {
     pub fn from_face(face: Face, pos: Vector3<f32>) -> Self {
+        Self::sized(face, pos, Vector3::new(1.0, 1.0, 1.0))
+    }
+
+    // quad spanning `size` along the two axes of the face plane, the normal axis is ignored
+    pub fn sized(face: Face, pos: Vector3<f32>, size: Vector3<f32>) -> Self {
         let vertices = match face {
             Face::Right => [
-                Vector3::new(pos.x, pos.y + 1.0, pos.z      ),
-                Vector3::new(pos.x, pos.y + 1.0, pos.z + 1.0),
-                Vector3::new(pos.x, pos.y,       pos.z + 1.0),
-                Vector3::new(pos.x, pos.y,       pos.z      ),
+                Vector3::new(pos.x,          pos.y + size.y, pos.z),
+                Vector3::new(pos.x,          pos.y + size.y, pos.z + size.z),
+                Vector3::new(pos.x,          pos.y,          pos.z + size.z),
+                Vector3::new(pos.x,          pos.y,          pos.z),
             ],
             Face::Left => [
-                Vector3::new(pos.x, pos.y,       pos.z      ),
-                Vector3::new(pos.x, pos.y,       pos.z + 1.0),
-                Vector3::new(pos.x, pos.y + 1.0, pos.z + 1.0),
-                Vector3::new(pos.x, pos.y + 1.0, pos.z      ),
+                Vector3::new(pos.x,          pos.y,          pos.z),
+                Vector3::new(pos.x,          pos.y,          pos.z + size.z),
+                Vector3::new(pos.x,          pos.y + size.y, pos.z + size.z),
+                Vector3::new(pos.x,          pos.y + size.y, pos.z),
             ],
             Face::Top => [
-                Vector3::new(pos.x,       pos.y, pos.z + 1.0),
-                Vector3::new(pos.x + 1.0, pos.y, pos.z + 1.0),
-                Vector3::new(pos.x + 1.0, pos.y, pos.z      ),
-                Vector3::new(pos.x,       pos.y, pos.z      ),
+                Vector3::new(pos.x,          pos.y,          pos.z + size.z),
+                Vector3::new(pos.x + size.x, pos.y,          pos.z + size.z),
+                Vector3::new(pos.x + size.x, pos.y,          pos.z),
+                Vector3::new(pos.x,          pos.y,          pos.z),
             ],
             Face::Bottom => [
-                Vector3::new(pos.x,       pos.y, pos.z      ),
-                Vector3::new(pos.x + 1.0, pos.y, pos.z      ),
-                Vector3::new(pos.x + 1.0, pos.y, pos.z + 1.0),
-                Vector3::new(pos.x,       pos.y, pos.z + 1.0),
+                Vector3::new(pos.x,          pos.y,          pos.z),
+                Vector3::new(pos.x + size.x, pos.y,          pos.z),
+                Vector3::new(pos.x + size.x, pos.y,          pos.z + size.z),
+                Vector3::new(pos.x,          pos.y,          pos.z + size.z),
             ],
             Face::Front => [
-                Vector3::new(pos.x + 1.0, pos.y,       pos.z),
-                Vector3::new(pos.x + 1.0, pos.y + 1.0, pos.z),
-                Vector3::new(pos.x,       pos.y + 1.0, pos.z),
-                Vector3::new(pos.x,       pos.y,       pos.z),
+                Vector3::new(pos.x + size.x, pos.y,          pos.z),
+                Vector3::new(pos.x + size.x, pos.y + size.y, pos.z),
+                Vector3::new(pos.x,          pos.y + size.y, pos.z),
+                Vector3::new(pos.x,          pos.y,          pos.z),
             ],
             Face::Back => [
-                Vector3::new(pos.x,       pos.y,       pos.z),
-                Vector3::new(pos.x,       pos.y + 1.0, pos.z),
-                Vector3::new(pos.x + 1.0, pos.y + 1.0, pos.z),
-                Vector3::new(pos.x + 1.0, pos.y,       pos.z),
+                Vector3::new(pos.x,          pos.y,          pos.z),
+                Vector3::new(pos.x,          pos.y + size.y, pos.z),
+                Vector3::new(pos.x + size.x, pos.y + size.y, pos.z),
+                Vector3::new(pos.x + size.x, pos.y,          pos.z),
             ],
         };
 
         Self {
-            vertices, face, color: wgpu::Color::WHITE,
+            vertices, face, color: 0, occlusion: [0; 4], light: 0,
         }
     }
 }
\ No newline at end of file
@@ -0,0 +1,161 @@
+use std::collections::HashMap;
+use cgmath::Vector3;
+
+use super::{
+    chunks::Chunks,
+    coords::{ChunkPos, WorldPos},
+    chunk::ChunkDims,
+    voxel::{Voxel, VoxelId},
+};
+
+// Inclusive box of voxels.
+#[derive(Debug, Clone, Copy)]
+pub struct Aabb {
+    pub min: WorldPos,
+    pub max: WorldPos,
+}
+
+impl Aabb {
+    pub fn new(a: WorldPos, b: WorldPos) -> Self {
+        Self {
+            min: WorldPos(Vector3::new(a.x.min(b.x), a.y.min(b.y), a.z.min(b.z))),
+            max: WorldPos(Vector3::new(a.x.max(b.x), a.y.max(b.y), a.z.max(b.z))),
+        }
+    }
+
+    pub fn chunk(chunk_pos: &ChunkPos) -> Self {
+        let origin = chunk_pos.origin();
+        Self::new(origin, origin + ChunkDims::get().extent() - Vector3::new(1, 1, 1))
+    }
+
+    fn contains(&self, pos: &WorldPos) -> bool {
+        (self.min.x..=self.max.x).contains(&pos.x)
+            && (self.min.y..=self.max.y).contains(&pos.y)
+            && (self.min.z..=self.max.z).contains(&pos.z)
+    }
+
+    fn intersection(&self, other: &Aabb) -> Option<Aabb> {
+        let min = Vector3::new(self.min.x.max(other.min.x), self.min.y.max(other.min.y), self.min.z.max(other.min.z));
+        let max = Vector3::new(self.max.x.min(other.max.x), self.max.y.min(other.max.y), self.max.z.min(other.max.z));
+        (min.x <= max.x && min.y <= max.y && min.z <= max.z).then_some(Aabb { min: WorldPos(min), max: WorldPos(max) })
+    }
+
+    // squared distance from `pos` to the closest voxel of the box
+    fn distance2(&self, pos: &WorldPos) -> i64 {
+        (0..3)
+            .map(|axis| {
+                let d = (self.min[axis] - pos[axis]).max(pos[axis] - self.max[axis]).max(0) as i64;
+                d * d
+            })
+            .sum()
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum Region {
+    Box(Aabb),
+    // voxels whose distance to `center` is at most `radius`
+    Sphere { center: WorldPos, radius: f32 },
+}
+
+impl Region {
+    pub fn bounds(&self) -> Aabb {
+        match self {
+            Self::Box(aabb) => *aabb,
+            Self::Sphere { center, radius } => {
+                let r = radius.max(0.0).floor() as i32;
+                Aabb::new(*center - Vector3::new(r, r, r), *center + Vector3::new(r, r, r))
+            }
+        }
+    }
+
+    pub fn contains(&self, pos: &WorldPos) -> bool {
+        match self {
+            Self::Box(aabb) => aabb.contains(pos),
+            Self::Sphere { center, radius } => distance2(center, pos) as f32 <= radius * radius,
+        }
+    }
+
+    fn overlaps(&self, aabb: &Aabb) -> bool {
+        match self {
+            Self::Box(bounds) => bounds.intersection(aabb).is_some(),
+            Self::Sphere { center, radius } => aabb.distance2(center) as f32 <= radius * radius,
+        }
+    }
+}
+
+// Query result plus the chunks the region touches that are not loaded yet,
+// whose voxels were left out.
+#[derive(Debug)]
+pub struct QueryResult<T> {
+    pub value: T,
+    pub unloaded: Vec<ChunkPos>,
+}
+
+impl Chunks {
+    // calls `visit` for every loaded voxel in the region, chunk by chunk
+    pub fn visit(&self, region: &Region, mut visit: impl FnMut(WorldPos, &Voxel)) -> Vec<ChunkPos> {
+        let bounds = region.bounds();
+        let (min_chunk, max_chunk) = (bounds.min.chunk(), bounds.max.chunk());
+        let mut unloaded = Vec::new();
+
+        for cx in min_chunk.x..=max_chunk.x {
+            for cy in min_chunk.y..=max_chunk.y {
+                for cz in min_chunk.z..=max_chunk.z {
+                    let chunk_pos = ChunkPos(Vector3::new(cx, cy, cz));
+                    let chunk_bounds = Aabb::chunk(&chunk_pos);
+                    let Some(part) = bounds.intersection(&chunk_bounds).filter(|part| region.overlaps(part)) else {
+                        continue;
+                    };
+
+                    if !self.is_chunk_loaded(&chunk_pos) {
+                        unloaded.push(chunk_pos);
+                        continue;
+                    }
+
+                    for x in part.min.x..=part.max.x {
+                        for y in part.min.y..=part.max.y {
+                            for z in part.min.z..=part.max.z {
+                                let pos = WorldPos(Vector3::new(x, y, z));
+                                if !region.contains(&pos) {
+                                    continue;
+                                }
+                                if let Ok(voxel) = self.try_get_voxel(&pos) {
+                                    visit(pos, voxel);
+                                }
+                            }
+                        }
+                    }
+                }
+            }
+        }
+        unloaded
+    }
+
+    pub fn count_blocks(&self, region: &Region) -> QueryResult<HashMap<VoxelId, usize>> {
+        let mut counts = HashMap::new();
+        let unloaded = self.visit(region, |_, voxel| *counts.entry(voxel.id).or_insert(0) += 1);
+        QueryResult { value: counts, unloaded }
+    }
+
+    // closest voxel within `max_distance` of `center` that matches `predicate`
+    pub fn find_nearest(
+        &self, center: WorldPos, max_distance: f32, predicate: impl Fn(&Voxel) -> bool
+    ) -> QueryResult<Option<WorldPos>> {
+
+        let mut nearest: Option<(i64, WorldPos)> = None;
+        let region = Region::Sphere { center, radius: max_distance };
+        let unloaded = self.visit(&region, |pos, voxel| {
+            let distance = distance2(&center, &pos);
+            if nearest.is_none_or(|(best, _)| distance < best) && predicate(voxel) {
+                nearest = Some((distance, pos));
+            }
+        });
+        QueryResult { value: nearest.map(|(_, pos)| pos), unloaded }
+    }
+}
+
+fn distance2(a: &WorldPos, b: &WorldPos) -> i64 {
+    let d = a.0 - b.0;
+    (d.x as i64).pow(2) + (d.y as i64).pow(2) + (d.z as i64).pow(2)
+}
@@ -0,0 +1,161 @@
+use std::{collections::HashMap, fs, path::{Path, PathBuf}, sync::OnceLock};
+use anyhow::{Context, Result};
+use serde::Deserialize;
+
+use crate::voxgl::rendering::arena::MAX_BLOCK_COLORS;
+
+use super::{quad::Face, voxel::{BlockState, VoxelId}};
+
+const BLOCKS_FILE: &str = "blocks.ron";
+const DEFAULT_BLOCKS: &str = include_str!("../../../resources/blocks.ron");
+// base, end and front color
+const COLORS_PER_BLOCK: usize = 3;
+
+static REGISTRY: OnceLock<BlockRegistry> = OnceLock::new();
+
+fn default_solid() -> bool {
+    true
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct Block {
+    pub name: String,
+    pub color: [f32; 4],
+    #[serde(default = "default_solid")]
+    pub solid: bool,
+    // color of the two faces along the voxel's axis (log ends)
+    #[serde(default)]
+    pub end_color: Option<[f32; 4]>,
+    // color of the face the voxel is facing
+    #[serde(default)]
+    pub front_color: Option<[f32; 4]>,
+
+    // lets neighbouring faces show through and is meshed separately
+    #[serde(default)]
+    pub transparent: bool,
+
+    // light emitted by the block, 0 to 15; lit faces ignore shading
+    #[serde(default)]
+    pub light: u8,
+    // image file, relative to resources/ in blocks.ron and resolved when the registry loads
+    #[serde(default)]
+    pub texture: Option<PathBuf>,
+}
+
+impl Block {
+    fn empty() -> Self {
+        Self {
+            name: "empty".to_string(),
+            color: [0.0, 0.0, 0.0, 1.0],
+            solid: false,
+            end_color: None,
+            front_color: None,
+            transparent: true,
+            light: 0,
+            texture: None,
+        }
+    }
+
+    // which of the block's colors a face shows: 0 base, 1 end, 2 front
+    fn color_slot(&self, state: BlockState, face: Face) -> usize {
+        match (self.front_color, self.end_color) {
+            (Some(_), _) if state.facing() == face => 2,
+            (_, Some(_)) if state.axis() == face.axis() => 1,
+            _ => 0,
+        }
+    }
+
+    fn colors(&self) -> [[f32; 4]; COLORS_PER_BLOCK] {
+        [self.color, self.end_color.unwrap_or(self.color), self.front_color.unwrap_or(self.color)]
+    }
+}
+
+pub struct BlockRegistry {
+    blocks: Vec<Block>,
+    ids: HashMap<String, VoxelId>,
+}
+
+impl BlockRegistry {
+    // reads resources/blocks.ron, falling back to the definitions built into the binary
+    pub fn load() -> Result<Self> {
+        let mut resources = std::env::current_dir()?;
+        resources.push("resources");
+        let path = resources.join(BLOCKS_FILE);
+
+        let mut registry = if path.exists() {
+            let source = fs::read_to_string(&path)?;
+            Self::from_ron(&source).with_context(|| format!("invalid block definitions {:?}", path))?
+        } else {
+            log::info!("no block definitions at {:?}, using built-in blocks", path);
+            Self::from_ron(DEFAULT_BLOCKS)?
+        };
+        registry.resolve_textures(&resources)?;
+        Ok(registry)
+    }
+
+    // turns texture paths into full paths, failing on any file that does not exist
+    fn resolve_textures(&mut self, resources: &Path) -> Result<()> {
+        for block in &mut self.blocks {
+            if let Some(texture) = &mut block.texture {
+                let resolved = resources.join(&*texture);
+                if !resolved.is_file() {
+                    anyhow::bail!("texture {:?} of block {:?} not found", resolved, block.name);
+                }
+                *texture = resolved;
+            }
+        }
+        Ok(())
+    }
+
+    pub fn from_ron(source: &str) -> Result<Self> {
+        let defs: Vec<Block> = ron::from_str(source)?;
+        let mut blocks = Vec::with_capacity(defs.len() + 1);
+        let mut ids = HashMap::with_capacity(defs.len() + 1);
+
+        for block in std::iter::once(Block::empty()).chain(defs) {
+            let id = VoxelId(u16::try_from(blocks.len()).context("too many blocks")?);
+            if ids.insert(block.name.clone(), id).is_some() {
+                anyhow::bail!("duplicate block {:?}", block.name);
+            }
+            blocks.push(block);
+        }
+
+        // every block takes COLORS_PER_BLOCK entries of the color table the shader indexes
+        let max_blocks = MAX_BLOCK_COLORS / COLORS_PER_BLOCK;
+        if blocks.len() > max_blocks {
+            anyhow::bail!("{} blocks do not fit the color table, at most {} are supported", blocks.len() - 1, max_blocks - 1);
+        }
+
+        Ok(Self { blocks, ids })
+    }
+
+    // installs the registry used by the world; must run before any chunk is generated
+    pub fn init(registry: BlockRegistry) -> Result<()> {
+        log::info!("loaded {} blocks", registry.blocks.len());
+        REGISTRY.set(registry).ok().context("block registry already initialized")
+    }
+
+    pub fn get() -> &'static BlockRegistry {
+        REGISTRY.get_or_init(|| Self::from_ron(DEFAULT_BLOCKS).expect("built-in block definitions are valid"))
+    }
+
+    pub fn block(&self, id: VoxelId) -> &Block {
+        self.blocks.get(id.0 as usize).unwrap_or(&self.blocks[0])
+    }
+
+    pub fn id(&self, name: &str) -> Option<VoxelId> {
+        self.ids.get(name).copied()
+    }
+
+    // every block color in registry order, COLORS_PER_BLOCK entries per block
+    pub fn color_table(&self) -> Vec<[f32; 4]> {
+        self.blocks.iter().flat_map(|block| block.colors()).collect()
+    }
+
+    // entry of the color table shown on a face of a block
+    pub fn color_index(&self, id: VoxelId, state: BlockState, face: Face) -> u32 {
+        // unknown ids show as empty, like `block`
+        let index = if (id.0 as usize) < self.blocks.len() { id.0 as usize } else { 0 };
+        (index * COLORS_PER_BLOCK + self.blocks[index].color_slot(state, face)) as u32
+    }
+}
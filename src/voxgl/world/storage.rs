@@ -0,0 +1,104 @@
+use std::{collections::HashMap, str::FromStr};
+use serde::Deserialize;
+
+use super::{
+    chunk::ChunkData,
+    coords::{ChunkPos, LocalPos},
+    octree::OctreeStorage,
+    voxel::Voxel,
+};
+
+// Where loaded chunk voxels live. Chunks are always generated into a dense `ChunkData` first;
+// each backend then keeps it in whatever layout suits it.
+pub trait VoxelStorage {
+    fn contains(&self, chunk_pos: &ChunkPos) -> bool;
+
+    fn chunk_count(&self) -> usize;
+
+    fn get_voxel(&self, chunk_pos: &ChunkPos, local_pos: &LocalPos) -> Option<&Voxel>;
+
+    // returns false if the chunk is not loaded
+    fn set_voxel(&mut self, chunk_pos: &ChunkPos, local_pos: &LocalPos, voxel: Voxel) -> bool;
+
+    // stores a generated chunk, handing back any `ChunkData` the backend no longer needs
+    fn insert(&mut self, chunk_pos: ChunkPos, chunk: ChunkData) -> Option<ChunkData>;
+
+    fn remove(&mut self, chunk_pos: &ChunkPos) -> Option<ChunkData>;
+
+    // removes every chunk, handing back the `ChunkData` the backend held
+    fn drain(&mut self) -> Vec<ChunkData>;
+}
+
+#[derive(Debug, Clone, Copy, Default, Deserialize)]
+pub enum StorageKind {
+    // one paletted array per chunk
+    #[default]
+    Dense,
+    // one sparse octree per chunk, cheaper for mostly empty worlds
+    Octree,
+}
+
+impl StorageKind {
+    pub fn create(self) -> Box<dyn VoxelStorage> {
+        match self {
+            Self::Dense => Box::new(DenseStorage::new()),
+            Self::Octree => Box::new(OctreeStorage::new()),
+        }
+    }
+}
+
+impl FromStr for StorageKind {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> anyhow::Result<Self> {
+        match s {
+            "dense" => Ok(Self::Dense),
+            "octree" => Ok(Self::Octree),
+            _ => anyhow::bail!("unknown storage {s}, expected dense or octree"),
+        }
+    }
+}
+
+pub struct DenseStorage {
+    chunks: HashMap<ChunkPos, ChunkData>,
+}
+
+impl DenseStorage {
+    pub fn new() -> Self {
+        Self { chunks: HashMap::new() }
+    }
+}
+
+impl VoxelStorage for DenseStorage {
+    fn contains(&self, chunk_pos: &ChunkPos) -> bool {
+        self.chunks.contains_key(chunk_pos)
+    }
+
+    fn chunk_count(&self) -> usize {
+        self.chunks.len()
+    }
+
+    fn get_voxel(&self, chunk_pos: &ChunkPos, local_pos: &LocalPos) -> Option<&Voxel> {
+        self.chunks.get(chunk_pos)?.get_voxel(local_pos)
+    }
+
+    fn set_voxel(&mut self, chunk_pos: &ChunkPos, local_pos: &LocalPos, voxel: Voxel) -> bool {
+        let Some(chunk) = self.chunks.get_mut(chunk_pos) else {
+            return false;
+        };
+        chunk.set_voxel(local_pos, voxel);
+        true
+    }
+
+    fn insert(&mut self, chunk_pos: ChunkPos, chunk: ChunkData) -> Option<ChunkData> {
+        self.chunks.insert(chunk_pos, chunk)
+    }
+
+    fn remove(&mut self, chunk_pos: &ChunkPos) -> Option<ChunkData> {
+        self.chunks.remove(chunk_pos)
+    }
+
+    fn drain(&mut self) -> Vec<ChunkData> {
+        self.chunks.drain().map(|(_, chunk)| chunk).collect()
+    }
+}
@@ -0,0 +1,151 @@
+use anyhow::{Context, Result};
+use cgmath::Vector3;
+use rand::{rngs::StdRng, Rng, SeedableRng};
+
+use super::{
+    biome::Biome,
+    chunk::{ChunkData, ChunkDims},
+    coords::{ChunkPos, LocalPos, WorldPos},
+    decorator::surface,
+    registry::BlockRegistry,
+    terrain::{ChunkColumns, Terrain},
+    voxel::Voxel,
+};
+
+// furthest a structure reaches from the ground voxel it is rooted on, on any axis
+pub const MAX_REACH: i32 = 8;
+
+const TREE_TRUNK: std::ops::RangeInclusive<i32> = 4..=6;
+const TREE_CROWN_RADIUS: i32 = 2;
+const RUIN_SIZE: i32 = 5;
+const RUIN_MAX_WALL: i32 = 3;
+
+#[derive(Debug, Clone, Copy)]
+enum Structure {
+    Tree,
+    Ruin,
+}
+
+// chance per surface column of rooting each structure, tried in order
+fn structures(biome: Biome) -> &'static [(Structure, f64)] {
+    match biome {
+        Biome::Plains => &[(Structure::Ruin, 0.0002), (Structure::Tree, 0.004)],
+        Biome::Tundra => &[(Structure::Tree, 0.002)],
+        Biome::Mountains => &[(Structure::Tree, 0.001)],
+        Biome::Desert => &[(Structure::Ruin, 0.0004)],
+        Biome::Beach => &[],
+    }
+}
+
+// Trees and ruins rooted in a chunk. They are generated from the chunk alone but may reach into
+// neighbouring chunks, so they come out as world space writes for `Chunks` to route.
+pub struct Structures {
+    seed: u64,
+    log: Voxel,
+    leaves: Voxel,
+    cobblestone: Voxel,
+}
+
+impl Structures {
+    pub fn new(seed: u64) -> Result<Self> {
+        let registry = BlockRegistry::get();
+        let block = |name: &str| {
+            registry.id(name).map(Voxel::from_id).with_context(|| format!("structures need block {name:?}"))
+        };
+
+        Ok(Self {
+            seed,
+            log: block("log")?,
+            leaves: block("leaves")?,
+            cobblestone: block("cobblestone")?,
+        })
+    }
+
+    // every write of every structure rooted in `chunk`, which must already be generated and decorated
+    pub fn generate(
+        &self, chunk: &ChunkData, chunk_pos: &ChunkPos, terrain: &Terrain, columns: &ChunkColumns
+    ) -> Vec<(WorldPos, Voxel)> {
+        let dims = ChunkDims::get();
+        let origin = chunk_pos.origin();
+        let mut rng = StdRng::seed_from_u64(self.chunk_seed(chunk_pos));
+        let mut writes = Vec::new();
+
+        for x in 0..dims.size {
+            for z in 0..dims.size {
+                let biome = columns.get(x, z).biome;
+                let Some(ground) = surface(chunk, chunk_pos, terrain, columns, x, z).filter(|ground| {
+                    chunk.get_voxel(ground).is_some_and(|v| v.id == terrain.surface_block(biome))
+                }) else {
+                    continue;
+                };
+
+                let ground = ground.0 + origin.0;
+                for (structure, chance) in structures(biome) {
+                    if rng.gen::<f64>() < *chance {
+                        match structure {
+                            Structure::Tree => self.tree(ground, &mut rng, &mut writes),
+                            Structure::Ruin => self.ruin(ground, &mut rng, &mut writes),
+                        }
+                        break;
+                    }
+                }
+            }
+        }
+        writes
+    }
+
+    fn tree(&self, ground: Vector3<i32>, rng: &mut StdRng, writes: &mut Vec<(WorldPos, Voxel)>) {
+        let trunk = rng.gen_range(TREE_TRUNK);
+        for dy in 1..=trunk {
+            writes.push((WorldPos(ground + Vector3::new(0, dy, 0)), self.log));
+        }
+
+        // the trunk goes first so the crown wraps around it instead of replacing it
+        let top = ground + Vector3::new(0, trunk, 0);
+        let r = TREE_CROWN_RADIUS;
+        for dx in -r..=r {
+            for dy in -1..=r {
+                for dz in -r..=r {
+                    if dx * dx + dy * dy + dz * dz <= r * r + 1 {
+                        writes.push((WorldPos(top + Vector3::new(dx, dy, dz)), self.leaves));
+                    }
+                }
+            }
+        }
+    }
+
+    // broken square of walls, sitting on the ground
+    fn ruin(&self, ground: Vector3<i32>, rng: &mut StdRng, writes: &mut Vec<(WorldPos, Voxel)>) {
+        for dx in 0..RUIN_SIZE {
+            for dz in 0..RUIN_SIZE {
+                let wall = dx == 0 || dz == 0 || dx == RUIN_SIZE - 1 || dz == RUIN_SIZE - 1;
+                if !wall {
+                    continue;
+                }
+                for dy in 1..=rng.gen_range(0..=RUIN_MAX_WALL) {
+                    writes.push((WorldPos(ground + Vector3::new(dx, dy, dz)), self.cobblestone));
+                }
+            }
+        }
+    }
+
+    fn chunk_seed(&self, chunk_pos: &ChunkPos) -> u64 {
+        // distinct from the decorator's mix so the two passes do not correlate
+        self.seed.rotate_left(17)
+            ^ (chunk_pos.x as u64).wrapping_mul(0xD6E8_FEB8_6659_FD93)
+            ^ (chunk_pos.y as u64).wrapping_mul(0xA076_1D64_78BD_642F)
+            ^ (chunk_pos.z as u64).wrapping_mul(0xE703_7ED1_A0B4_28DB)
+    }
+}
+
+// how many chunks around a chunk may write structures into it, per axis
+pub fn chunk_margin() -> Vector3<i32> {
+    ChunkDims::get().extent().map(|edge| (MAX_REACH + edge - 1) / edge)
+}
+
+// structures only fill air, so they neither replace terrain nor punch into water
+pub fn place_structure_voxel(chunk: &mut ChunkData, pos: &LocalPos, voxel: Voxel) {
+    if chunk.get_voxel(pos).is_some_and(|v| !v.is_visible()) {
+        chunk.set_voxel(pos, voxel);
+    }
+}
@@ -1,51 +1,384 @@
+use anyhow::{Context, Result};
 use opensimplex_noise_rs::OpenSimplexNoise;
 
-use crate::voxgl::world::chunk::ChunkData;
+use super::{
+    biome::{Biome, BiomeMap, BiomeWeights},
+    chunk::{ChunkData, ChunkDims},
+    coords::{ChunkPos, LocalPos},
+    density::{Density, DensityField},
+    erosion::Erosion,
+    height_image::HeightImage,
+    generator::TerrainGenerator,
+    registry::BlockRegistry,
+    terrain_config::{Octave, TerrainConfig},
+    voxel::{BlockState, Voxel, VoxelId},
+};
 
-use super::{voxel::VoxelId, chunk::CHUNK_SIZE};
+pub const DEFAULT_SEA_LEVEL: i32 = 8;
 
-impl ChunkData {
-    pub fn build_voxel_data(&mut self, chunk_world_pos: &cgmath::Vector3<f32>) {
-        let generator = OpenSimplexNoise::new(None);
+// Natural noise terrain: biome heights, optionally reshaped by a 3D density field, flooded
+// up to sea level.
+pub struct Terrain {
+    sea_level: i32,
+    subsurface_depth: i32,
+    beach_margin: i32,
+    noise: OpenSimplexNoise,
+    octaves: Vec<Octave>,
+    amplitude: f64,
+    biomes: BiomeMap,
+    density: Option<DensityField>,
+    erosion: Option<Erosion>,
+    // imported heights replacing the noise
+    image: Option<HeightImage>,
+    blocks: TerrainBlocks,
+}
+
+struct TerrainBlocks {
+    stone: VoxelId,
+    // still water filling its whole voxel
+    water: Voxel,
+    // (surface, sub-surface) for each biome, indexed by `Biome as usize`
+    biomes: [(VoxelId, VoxelId); Biome::ALL.len()],
+}
+
+// Generated shape of one x/z column.
+pub struct Column {
+    pub height: i32,
+    pub biome: Biome,
+    // air below this height is water: the sea, or a river or lake above it
+    pub water_level: i32,
+}
+
+// Columns of one chunk, computed once and shared by the terrain, decorator and structures.
+pub struct ChunkColumns {
+    columns: Vec<Column>,
+}
+
+impl ChunkColumns {
+    // column at (x, z) within the chunk
+    pub fn get(&self, x: i32, z: i32) -> &Column {
+        &self.columns[(x * ChunkDims::get().size + z) as usize]
+    }
+}
 
-        for (index, voxel) in self.voxels.iter_mut().enumerate() {
-            let local_pos = Self::get_local_pos(index as i32);
+impl Terrain {
+    pub fn new(seed: u64, sea_level: i32, density: bool, config: &TerrainConfig) -> Result<Self> {
+        let registry = BlockRegistry::get();
+        let block = |name: &str| registry.id(name).with_context(|| format!("terrain needs block {name:?}"));
 
-            let x = chunk_world_pos.x + local_pos.x as f32;
-            let y = (chunk_world_pos.y + local_pos.y as f32) as i32;
-            let z = chunk_world_pos.z + local_pos.z as f32;
-            
-            let height = get_height(&generator, x as f64, z as f64);
-            
-            if y < height - 1 {
-                voxel.id = VoxelId::Stone;
-            } 
-            if y >= 8 && y < height {
-                voxel.id = VoxelId::Grass;
+        let mut biomes = [(VoxelId::EMPTY, VoxelId::EMPTY); Biome::ALL.len()];
+        for biome in Biome::ALL {
+            let (surface, subsurface) = biome.blocks();
+            biomes[biome as usize] = (block(surface)?, block(subsurface)?);
+        }
+
+        Ok(Self {
+            sea_level,
+            subsurface_depth: config.subsurface_depth,
+            beach_margin: config.beach_margin,
+            noise: OpenSimplexNoise::new(Some(seed as i64)),
+            octaves: config.octaves.clone(),
+            amplitude: config.amplitude(),
+            biomes: BiomeMap::new(seed, config),
+            density: density.then(|| DensityField::new(seed)),
+            erosion: config.erosion.enabled.then(|| Erosion::new(seed, sea_level, &config.erosion)),
+            image: None,
+            blocks: TerrainBlocks {
+                stone: block("stone")?,
+                water: Voxel::from_id(block("water")?).with_state(BlockState::default().with_level(BlockState::FULL_LEVEL)),
+                biomes,
+            },
+        })
+    }
+
+    // builds columns from an imported heightmap instead of noise; the map is kept as drawn, so
+    // it is not eroded
+    pub fn with_image(mut self, image: HeightImage) -> Self {
+        self.image = Some(image);
+        self.erosion = None;
+        self
+    }
+
+    pub fn column(&self, x: i32, z: i32) -> Column {
+        // one set of climate weights gives both the noise height and the dominant biome
+        let painted = self.image.as_ref().and_then(|image| image.biome(x, z));
+        let weights = painted.is_none().then(|| self.biomes.weights(x as f64, z as f64));
+
+        let (height, water_level) = match &self.erosion {
+            Some(erosion) => {
+                let column = erosion.column(x, z, &|x, z| self.base_height(x, z, None));
+                (column.height, self.sea_level.max(column.water as i32))
             }
-            if y >= 6 && y < height - 8 {
-                voxel.id = VoxelId::Sand;
+            None => (self.base_height(x, z, weights.as_ref()), self.sea_level),
+        };
+        let height = height.max(1.0) as i32;
+
+        // a painted biome map is taken as is, climate biomes get beaches along the water
+        let biome = match (painted, weights) {
+            (Some(biome), _) => biome,
+            (None, weights) => {
+                let biome = weights.map_or(Biome::Plains, |weights| weights.dominant());
+                if height <= self.sea_level + self.beach_margin && biome != Biome::Mountains {
+                    Biome::Beach
+                } else {
+                    biome
+                }
             }
+        };
+        Column { height, biome, water_level }
+    }
+
+    // every column of a chunk, indexed by local x and z
+    pub fn columns(&self, chunk_pos: &ChunkPos) -> ChunkColumns {
+        let dims = ChunkDims::get();
+        let origin = chunk_pos.origin();
+        let columns = (0..dims.size)
+            .flat_map(|x| (0..dims.size).map(move |z| (x, z)))
+            .map(|(x, z)| self.column(origin.x + x, origin.z + z))
+            .collect();
+        ChunkColumns { columns }
+    }
+
+    // height of the column before erosion; `weights` are the climate weights at (x, z) when
+    // the caller already has them
+    fn base_height(&self, x: i32, z: i32, weights: Option<&BiomeWeights>) -> f64 {
+        if let Some(image) = &self.image {
+            return image.height(x, z);
         }
+
+        let noise = self.noise(x as f64, z as f64);
+        match weights {
+            Some(weights) => weights.height(noise),
+            None => self.biomes.weights(x as f64, z as f64).height(noise),
+        }
+    }
+
+    // whether generation leaves the voxel at (x, y, z) of `column` empty; air under the water
+    // level counts as water, since only the full column walk knows whether it is open to the sky
+    pub fn is_air(&self, column: &Column, x: i32, y: i32, z: i32) -> bool {
+        y >= column.water_level
+            && match &self.density {
+                Some(density) => !density.is_solid(x, y, z, column.height),
+                None => y >= column.height,
+            }
+    }
+
+    pub fn biome_at(&self, x: i32, z: i32) -> Biome {
+        self.column(x, z).biome
+    }
+
+    pub fn surface_block(&self, biome: Biome) -> VoxelId {
+        self.layer_block(biome, 0)
+    }
+
+    // block for a solid voxel `depth` voxels below the nearest air above it
+    fn layer_block(&self, biome: Biome, depth: i32) -> VoxelId {
+        let (surface, subsurface) = self.blocks.biomes[biome as usize];
+        match depth {
+            0 => surface,
+            depth if depth <= self.subsurface_depth => subsurface,
+            _ => self.blocks.stone,
+        }
+    }
+
+    // fractal noise in -1..1, independent of the chunk dimensions so the terrain looks the same
+    // at every chunk size; biomes scale it into heights
+    fn noise(&self, x: f64, z: f64) -> f64 {
+        let noise: f64 = self.octaves.iter()
+            .map(|octave| self.noise.eval_2d(x * octave.frequency, z * octave.frequency) * octave.amplitude)
+            .sum();
+        noise / self.amplitude
     }
 }
 
-fn get_height(generator: &OpenSimplexNoise, x: f64, z: f64) -> i32 {
-    let mut a1 = CHUNK_SIZE as f64 * 2.0;
-    let (a2, a4, a8) = (a1 * 0.5, a1 * 0.25, a1 * 0.125);
+impl TerrainGenerator for Terrain {
+    fn generate(&self, chunk: &mut ChunkData, chunk_pos: &ChunkPos) {
+        self.fill(chunk, chunk_pos, &self.columns(chunk_pos));
+    }
 
-    let f1 = 0.005;
-    let (f2, f4, f8) = (f1 * 2.0, f1 * 4.0, f1 * 8.0);
+    fn terrain(&self) -> Option<&Terrain> {
+        Some(self)
+    }
+}
 
-    if generator.eval_2d(x * 0.1, z * 0.1) < 0.0 {
-        a1 /= 1.04;
+impl Terrain {
+    pub fn fill(&self, chunk: &mut ChunkData, chunk_pos: &ChunkPos, columns: &ChunkColumns) {
+        let dims = ChunkDims::get();
+        for x in 0..dims.size {
+            for z in 0..dims.size {
+                let column = columns.get(x, z);
+                match &self.density {
+                    Some(density) => self.fill_density_column(chunk, density, column, chunk_pos, x, z),
+                    None => self.fill_height_column(chunk, column, chunk_pos, x, z),
+                }
+            }
+        }
+        chunk.voxels.compact();
+    }
+
+    fn fill_height_column(&self, chunk: &mut ChunkData, column: &Column, chunk_pos: &ChunkPos, x: i32, z: i32) {
+        let origin = chunk_pos.origin();
+        for y in 0..ChunkDims::get().height {
+            let depth = column.height - 1 - (origin.y + y);
+            let voxel = if depth >= 0 {
+                Voxel::from_id(self.layer_block(column.biome, depth))
+            } else if origin.y + y < column.water_level {
+                self.blocks.water
+            } else {
+                continue;
+            };
+            chunk.set_voxel(&LocalPos::new(x, y, z), voxel);
+        }
+    }
+
+    fn fill_density_column(
+        &self, chunk: &mut ChunkData, density: &DensityField, column: &Column, chunk_pos: &ChunkPos, x: i32, z: i32
+    ) {
+        let dims = ChunkDims::get();
+        let origin = chunk_pos.origin();
+        let (world_x, world_z) = (origin.x + x, origin.z + z);
+
+        // walk down from far enough above the chunk that its top voxel gets its real depth, so
+        // layers continue across chunk borders; where the column can hold water, start above any
+        // solid voxel to know what is open to the sky
+        let reach = DensityField::reach();
+        let mut top = origin.y + dims.height + self.subsurface_depth + 1;
+        if column.height - reach < column.water_level {
+            top = top.max(column.height + reach);
+        }
+
+        // a walk starting below every overhang is in rock, at least this deep under the surface
+        let mut depth = (top <= column.height - reach).then_some(column.height - reach - 1 - top);
+        let mut open_to_sky = top >= column.height + reach;
+        for world_y in (origin.y..top).rev() {
+            let sample = density.sample(world_x, world_y, world_z, column.height);
+            depth = match sample {
+                Density::Solid => {
+                    open_to_sky = false;
+                    Some(depth.map_or(0, |d| d + 1))
+                }
+                // caves keep counting the depth from the surface, so their floors are rock and not grass
+                Density::Cave => depth.map(|d| d + 1),
+                Density::Open => None,
+            };
+
+            let y = world_y - origin.y;
+            if y >= dims.height {
+                continue;
+            }
+
+            // only air open to the sky floods, caves and overhangs stay dry
+            let voxel = match (sample, depth) {
+                (Density::Solid, Some(depth)) => Voxel::from_id(self.layer_block(column.biome, depth)),
+                _ if open_to_sky && world_y < column.water_level => self.blocks.water,
+                _ => continue,
+            };
+            chunk.set_voxel(&LocalPos::new(x, y, z), voxel);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use cgmath::Vector3;
+
+    use super::*;
+
+    fn density_terrain(seed: u64) -> Terrain {
+        Terrain::new(seed, DEFAULT_SEA_LEVEL, true, &TerrainConfig::default()).unwrap()
     }
 
-    let mut height = 0.0;
-    height += generator.eval_2d(x * f1, z * f1) * a1 + a1;
-    height += generator.eval_2d(x * f2, z * f2) * a2 - a2;
-    height += generator.eval_2d(x * f4, z * f4) * a4 + a4;
-    height += generator.eval_2d(x * f8, z * f8) * a8 - a8;
+    fn generate(terrain: &Terrain, chunk_pos: &ChunkPos) -> ChunkData {
+        let mut chunk = ChunkData::new();
+        terrain.generate(&mut chunk, chunk_pos);
+        chunk
+    }
+
+    fn voxels(chunk: &ChunkData) -> Vec<Voxel> {
+        let dims = ChunkDims::get();
+        let mut voxels = Vec::new();
+        for x in 0..dims.size {
+            for y in 0..dims.height {
+                for z in 0..dims.size {
+                    voxels.push(*chunk.get_voxel(&LocalPos::new(x, y, z)).unwrap());
+                }
+            }
+        }
+        voxels
+    }
 
-    height.max(1.0) as i32
-}
\ No newline at end of file
+    #[test]
+    fn same_seed_same_chunk() {
+        for chunk_pos in [ChunkPos(Vector3::new(0, 0, 0)), ChunkPos(Vector3::new(-3, 1, 5))] {
+            let first = voxels(&generate(&density_terrain(7), &chunk_pos));
+            let second = voxels(&generate(&density_terrain(7), &chunk_pos));
+            assert!(first == second, "chunk {:?} differs between runs", chunk_pos.0);
+        }
+    }
+
+    #[test]
+    fn buried_chunk_tops_are_stone() {
+        // no sea or rivers, so the walk down each column starts just above the chunk
+        let mut config = TerrainConfig::default();
+        config.erosion.enabled = false;
+        let terrain = Terrain::new(7, -1000, true, &config).unwrap();
+        let density = terrain.density.as_ref().unwrap();
+        let dims = ChunkDims::get();
+        let chunk_pos = ChunkPos(Vector3::new(0, -3, 0));
+        let chunk = generate(&terrain, &chunk_pos);
+        let origin = chunk_pos.origin();
+        let top = origin.y + dims.height - 1;
+
+        // columns with rock all the way through the layers above the chunk
+        let mut buried = 0;
+        for x in 0..dims.size {
+            for z in 0..dims.size {
+                let (world_x, world_z) = (origin.x + x, origin.z + z);
+                let height = terrain.column(world_x, world_z).height;
+                if (top..=top + terrain.subsurface_depth + 1).all(|y| density.is_solid(world_x, y, world_z, height)) {
+                    buried += 1;
+                    let voxel = chunk.get_voxel(&LocalPos::new(x, dims.height - 1, z)).unwrap();
+                    assert_eq!(voxel.id, terrain.blocks.stone, "seam at column ({world_x}, {world_z})");
+                }
+            }
+        }
+        assert!(buried > 0);
+    }
+
+    #[test]
+    fn cave_floors_are_not_surface() {
+        let terrain = density_terrain(7);
+        let density = terrain.density.as_ref().unwrap();
+        let dims = ChunkDims::get();
+
+        let mut checked = 0;
+        for (cx, cy, cz) in (-2..2).flat_map(|x| (-3..1).flat_map(move |y| (-2..2).map(move |z| (x, y, z)))) {
+            let chunk_pos = ChunkPos(Vector3::new(cx, cy, cz));
+            let chunk = generate(&terrain, &chunk_pos);
+            let origin = chunk_pos.origin();
+            for x in 0..dims.size {
+                for z in 0..dims.size {
+                    let (world_x, world_z) = (origin.x + x, origin.z + z);
+                    let column = terrain.column(world_x, world_z);
+                    let surface = terrain.surface_block(column.biome);
+                    if surface == terrain.layer_block(column.biome, 1) {
+                        continue;
+                    }
+
+                    for y in 0..dims.height {
+                        if chunk.get_voxel(&LocalPos::new(x, y, z)).unwrap().id != surface {
+                            continue;
+                        }
+                        // going up through any caves has to come out in open air, not under rock
+                        let above = (origin.y + y + 1..)
+                            .map(|y| density.sample(world_x, y, world_z, column.height))
+                            .find(|sample| *sample != Density::Cave);
+                        assert_eq!(above, Some(Density::Open), "surface block at ({world_x}, {}, {world_z})", origin.y + y);
+                        checked += 1;
+                    }
+                }
+            }
+        }
+        assert!(checked > 0);
+    }
+}
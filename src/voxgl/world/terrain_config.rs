@@ -0,0 +1,167 @@
+use std::fs;
+use anyhow::{Context, Result};
+use serde::Deserialize;
+
+use super::biome::HeightParams;
+
+const TERRAIN_FILE: &str = "terrain.ron";
+
+// Tuning knobs of the noise terrain, read from resources/terrain.ron and reloadable at runtime.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct TerrainConfig {
+    // height noise layers, summed and scaled back into -1..1 by the total amplitude
+    pub octaves: Vec<Octave>,
+    // depth of the biome's sub-surface layer before it turns into stone
+    pub subsurface_depth: i32,
+    // lowland columns up to this far above sea level turn into beach
+    pub beach_margin: i32,
+    // frequency of the climate noise, lower means larger biomes
+    pub climate_scale: f64,
+    // higher values make biome borders sharper
+    pub blend_sharpness: i32,
+    pub biomes: BiomeShapes,
+    pub erosion: ErosionConfig,
+}
+
+#[derive(Debug, Clone, Copy, Deserialize)]
+pub struct Octave {
+    pub frequency: f64,
+    pub amplitude: f64,
+}
+
+// Height of each climate biome; beaches follow the terrain they replace.
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(default)]
+pub struct BiomeShapes {
+    pub desert: HeightParams,
+    pub plains: HeightParams,
+    pub mountains: HeightParams,
+    pub tundra: HeightParams,
+}
+
+// Droplet erosion and river carving, see `erosion::Erosion`.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct ErosionConfig {
+    pub enabled: bool,
+    // columns per region side; regions simulate twice this so they can blend into each other
+    pub region_size: i32,
+    // droplets simulated per column
+    pub droplets: f64,
+    // steps a droplet runs before it evaporates
+    pub lifetime: u32,
+    // how much a droplet keeps its direction instead of following the slope, 0..1
+    pub inertia: f32,
+    // sediment a droplet can carry per unit of drop, speed and water
+    pub capacity: f32,
+    pub min_capacity: f32,
+    // fraction of free capacity eroded and of excess sediment deposited per step
+    pub erode_speed: f32,
+    pub deposit_speed: f32,
+    pub evaporation: f32,
+    pub gravity: f32,
+    // upstream columns draining through a column before it becomes a river
+    pub river_threshold: u32,
+    // deepest a river bed is carved below its banks
+    pub river_depth: f32,
+    // how far rivers wander off the steepest way down
+    pub meander: f32,
+}
+
+impl Default for TerrainConfig {
+    fn default() -> Self {
+        Self {
+            octaves: vec![
+                Octave { frequency: 0.005, amplitude: 1.0 },
+                Octave { frequency: 0.01, amplitude: 0.5 },
+                Octave { frequency: 0.02, amplitude: 0.25 },
+                Octave { frequency: 0.04, amplitude: 0.125 },
+            ],
+            subsurface_depth: 3,
+            beach_margin: 2,
+            climate_scale: 0.002,
+            blend_sharpness: 4,
+            biomes: BiomeShapes::default(),
+            erosion: ErosionConfig::default(),
+        }
+    }
+}
+
+impl Default for ErosionConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            region_size: 64,
+            droplets: 0.5,
+            lifetime: 32,
+            inertia: 0.05,
+            capacity: 4.0,
+            min_capacity: 0.01,
+            erode_speed: 0.3,
+            deposit_speed: 0.3,
+            evaporation: 0.02,
+            gravity: 4.0,
+            river_threshold: 400,
+            river_depth: 5.0,
+            meander: 0.5,
+        }
+    }
+}
+
+impl Default for BiomeShapes {
+    fn default() -> Self {
+        Self {
+            desert: HeightParams { base_height: 14.0, amplitude: 10.0 },
+            plains: HeightParams { base_height: 16.0, amplitude: 14.0 },
+            mountains: HeightParams { base_height: 30.0, amplitude: 56.0 },
+            tundra: HeightParams { base_height: 18.0, amplitude: 20.0 },
+        }
+    }
+}
+
+impl TerrainConfig {
+    // reads resources/terrain.ron, using the defaults when it does not exist
+    pub fn load() -> Result<Self> {
+        let mut path = std::env::current_dir()?;
+        path.push("resources");
+        path.push(TERRAIN_FILE);
+
+        if !path.exists() {
+            log::info!("no terrain config at {:?}, using defaults", path);
+            return Ok(Self::default());
+        }
+
+        let source = fs::read_to_string(&path)?;
+        let config: Self = ron::from_str(&source).with_context(|| format!("invalid terrain config {:?}", path))?;
+        config.validate().with_context(|| format!("invalid terrain config {:?}", path))?;
+        Ok(config)
+    }
+
+    // total amplitude of the octaves, which scales their sum back into -1..1
+    pub fn amplitude(&self) -> f64 {
+        self.octaves.iter().map(|octave| octave.amplitude).sum()
+    }
+
+    fn validate(&self) -> Result<()> {
+        if self.amplitude() <= 0.0 {
+            anyhow::bail!("octaves need a positive total amplitude");
+        }
+        if self.subsurface_depth < 0 || self.beach_margin < 0 {
+            anyhow::bail!("subsurface_depth and beach_margin must not be negative");
+        }
+        if self.climate_scale <= 0.0 || self.blend_sharpness <= 0 {
+            anyhow::bail!("climate_scale and blend_sharpness must be positive");
+        }
+        if self.erosion.region_size < 8 || self.erosion.region_size % 2 != 0 {
+            anyhow::bail!("erosion region_size must be even and at least 8");
+        }
+        if self.erosion.river_threshold == 0 {
+            anyhow::bail!("erosion river_threshold must be positive");
+        }
+        if self.erosion.meander < 0.0 {
+            anyhow::bail!("erosion meander must not be negative");
+        }
+        Ok(())
+    }
+}
@@ -1,33 +1,112 @@
+use super::{quad::Face, registry::BlockRegistry};
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
-pub enum VoxelId {
-    Grass, Empty, Sand, Dirt, Stone, Snow
-}
+pub struct VoxelId(pub u16);
 
 impl VoxelId {
-    pub fn get_color(&self) -> wgpu::Color {
-        match self {
-            VoxelId::Empty => wgpu::Color { r: 0.00, g: 0.00, b: 0.00,  a: 1.0 },
-            VoxelId::Grass => wgpu::Color { r: 0.21, g: 0.80, b: 0.01,  a: 1.0 },
-            VoxelId::Sand  => wgpu::Color { r: 1.00, g: 0.88, b: 0.31,  a: 1.0 },
-            VoxelId::Dirt  => wgpu::Color { r: 0.29, g: 0.20, b: 0.15,  a: 1.0 },
-            VoxelId::Stone => wgpu::Color { r: 0.55, g: 0.55, b: 0.55,  a: 1.0 },
-            VoxelId::Snow  => wgpu::Color { r: 0.97, g: 0.95, b: 0.97,  a: 1.0 },
+    pub const EMPTY: VoxelId = VoxelId(0);
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Axis {
+    X, Y, Z
+}
+
+// Packed per-voxel state. What each field means is up to the block:
+//   bits 0..3   facing (oriented blocks)
+//   bits 3..5   axis (logs, pillars)
+//   bits 5..9   level (fluids), 15 when full
+//   bits 9..13  growth stage (plants), 15 when fully grown
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct BlockState(pub u16);
+
+const FACING_SHIFT: u16 = 0;
+const AXIS_SHIFT: u16 = 3;
+const LEVEL_SHIFT: u16 = 5;
+const GROWTH_SHIFT: u16 = 9;
+
+impl BlockState {
+    // fluid that fills its whole voxel, as generated
+    pub const FULL_LEVEL: u8 = 15;
+    // plant that has finished growing, as decorated
+    pub const MAX_GROWTH: u8 = 15;
+
+    pub fn facing(&self) -> Face {
+        Face::ALL[(self.get(FACING_SHIFT, 0b111) as usize).min(Face::ALL.len() - 1)]
+    }
+
+    pub fn with_facing(self, facing: Face) -> Self {
+        self.with(FACING_SHIFT, 0b111, facing as u16)
+    }
+
+    pub fn axis(&self) -> Axis {
+        match self.get(AXIS_SHIFT, 0b11) {
+            0 => Axis::Y,
+            1 => Axis::X,
+            _ => Axis::Z,
         }
     }
+
+    pub fn with_axis(self, axis: Axis) -> Self {
+        let bits = match axis {
+            Axis::Y => 0,
+            Axis::X => 1,
+            Axis::Z => 2,
+        };
+        self.with(AXIS_SHIFT, 0b11, bits)
+    }
+
+    pub fn with_level(self, level: u8) -> Self {
+        self.with(LEVEL_SHIFT, 0b1111, level as u16)
+    }
+
+    pub fn with_growth(self, growth: u8) -> Self {
+        self.with(GROWTH_SHIFT, 0b1111, growth as u16)
+    }
+
+    fn get(&self, shift: u16, mask: u16) -> u16 {
+        (self.0 >> shift) & mask
+    }
+
+    fn with(self, shift: u16, mask: u16, value: u16) -> Self {
+        Self((self.0 & !(mask << shift)) | ((value & mask) << shift))
+    }
 }
 
-#[derive(Debug, Copy, Clone)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
 pub struct Voxel {
     pub id: VoxelId,
+    pub state: BlockState,
 }
 
 impl Voxel {
     pub fn new() -> Self {
-        Self { id: VoxelId::Empty }
+        Self::from_id(VoxelId::EMPTY)
+    }
+
+    pub fn from_id(id: VoxelId) -> Self {
+        Self { id, state: BlockState::default() }
+    }
+
+    pub fn with_state(self, state: BlockState) -> Self {
+        Self { state, ..self }
     }
 
     pub fn is_solid(&self) -> bool {
-        self.id != VoxelId::Empty 
+        BlockRegistry::get().block(self.id).solid
+    }
+
+    pub fn is_visible(&self) -> bool {
+        self.id != VoxelId::EMPTY
+    }
+
+    pub fn is_transparent(&self) -> bool {
+        BlockRegistry::get().block(self.id).transparent
+    }
+
+    // whether this voxel hides the face of `other` that touches it; opaque blocks hide
+    // everything, transparent ones only hide faces of the same block (water next to water)
+    pub fn occludes(&self, other: &Voxel) -> bool {
+        !self.is_transparent() || (self.is_visible() && self.id == other.id)
     }
 }
@@ -0,0 +1,297 @@
+use std::{
+    collections::{BTreeMap, HashMap, HashSet},
+    sync::{mpsc::{self, Receiver, Sender}, Arc, Mutex},
+    thread,
+};
+
+use super::{
+    chunk::ChunkData,
+    coords::{ChunkPos, WorldPos},
+    generator::WorldGenerator,
+    mesh_builder::{self, ChunkMeshData, ChunkSnapshot, MesherKind},
+    voxel::Voxel,
+};
+
+// Starts one worker per core, leaving a core for the render thread. Each runs `work` on the jobs
+// sent to the returned sender and sends the results back; workers stop once either side is dropped.
+fn spawn_workers<J: Send + 'static, R: Send + 'static>(name: &str, work: fn(J) -> R) -> (Sender<J>, Receiver<R>) {
+    let threads = thread::available_parallelism().map_or(1, |n| n.get().saturating_sub(1).max(1));
+    let (jobs, job_receiver) = mpsc::channel::<J>();
+    let (result_sender, results) = mpsc::channel();
+    let job_receiver = Arc::new(Mutex::new(job_receiver));
+
+    for index in 0..threads {
+        let jobs = job_receiver.clone();
+        let results = result_sender.clone();
+        thread::Builder::new()
+            .name(format!("{name}-worker-{index}"))
+            .spawn(move || loop {
+                // the lock is released as soon as a job is taken
+                let Ok(job) = jobs.lock().unwrap().recv() else {
+                    return;
+                };
+                if results.send(work(job)).is_err() {
+                    return;
+                }
+            })
+            .expect("failed to spawn worker");
+    }
+    log::info!("{name} workers: {threads}");
+
+    (jobs, results)
+}
+
+// Chunk handed to a worker, tagged so its result can be put back in order.
+struct GenerationJob {
+    ticket: u64,
+    epoch: u64,
+    chunk_pos: ChunkPos,
+    chunk: ChunkData,
+    world: Arc<WorldGenerator>,
+}
+
+// Generated chunk on its way back to the main thread, with the structure voxels it placed.
+pub struct GeneratedChunk {
+    ticket: u64,
+    epoch: u64,
+    pub chunk_pos: ChunkPos,
+    pub chunk: ChunkData,
+    pub structures: Vec<(WorldPos, Voxel)>,
+}
+
+// Pool of threads generating chunk data off the main thread.
+//
+// Workers finish in any order, so results are held back until every chunk submitted before them
+// is done: the main thread applies them in submission order, exactly as if they had been
+// generated one after the other.
+pub struct GenerationWorkers {
+    jobs: Sender<GenerationJob>,
+    results: Receiver<GeneratedChunk>,
+    in_flight: HashSet<ChunkPos>,
+    // results that arrived ahead of an earlier ticket
+    finished: BTreeMap<u64, GeneratedChunk>,
+    next_ticket: u64,
+    next_result: u64,
+    // bumped by `cancel_all`; results from older epochs are discarded
+    epoch: u64,
+    // chunks of cancelled jobs, waiting to go back to the pool
+    discarded: Vec<ChunkData>,
+}
+
+impl GenerationWorkers {
+    pub fn new() -> Self {
+        let (jobs, results) = spawn_workers("generation", |job: GenerationJob| {
+            let GenerationJob { ticket, epoch, chunk_pos, mut chunk, world } = job;
+            let structures = world.generate(&mut chunk, &chunk_pos);
+            GeneratedChunk { ticket, epoch, chunk_pos, chunk, structures }
+        });
+
+        Self {
+            jobs,
+            results,
+            in_flight: HashSet::new(),
+            finished: BTreeMap::new(),
+            next_ticket: 0,
+            next_result: 0,
+            epoch: 0,
+            discarded: Vec::new(),
+        }
+    }
+
+    // queues an empty chunk for generation with `world`
+    pub fn submit(&mut self, chunk_pos: ChunkPos, chunk: ChunkData, world: &Arc<WorldGenerator>) {
+        let job = GenerationJob { ticket: self.next_ticket, epoch: self.epoch, chunk_pos, chunk, world: world.clone() };
+        self.next_ticket += 1;
+        self.in_flight.insert(chunk_pos);
+        self.jobs.send(job).expect("chunk workers stopped");
+    }
+
+    pub fn in_flight(&self) -> usize {
+        self.in_flight.len()
+    }
+
+    pub fn is_generating(&self, chunk_pos: &ChunkPos) -> bool {
+        self.in_flight.contains(chunk_pos)
+    }
+
+    // chunks finished since the last call, in the order they were submitted
+    pub fn collect(&mut self) -> Vec<GeneratedChunk> {
+        while let Ok(result) = self.results.try_recv() {
+            if result.epoch == self.epoch {
+                self.finished.insert(result.ticket, result);
+            } else {
+                self.discarded.push(result.chunk);
+            }
+        }
+
+        let mut ready = Vec::new();
+        while let Some(result) = self.finished.remove(&self.next_result) {
+            self.in_flight.remove(&result.chunk_pos);
+            self.next_result += 1;
+            ready.push(result);
+        }
+        ready
+    }
+
+    // chunk data of cancelled jobs, to be reused
+    pub fn take_discarded(&mut self) -> Vec<ChunkData> {
+        std::mem::take(&mut self.discarded)
+    }
+
+    // forgets every submitted chunk; workers finish them but the results are discarded
+    pub fn cancel_all(&mut self) {
+        self.epoch += 1;
+        self.in_flight.clear();
+        let finished = std::mem::take(&mut self.finished);
+        self.discarded.extend(finished.into_values().map(|result| result.chunk));
+        self.next_ticket = 0;
+        self.next_result = 0;
+    }
+}
+
+// Snapshot handed to a mesh worker, with the version of the chunk it was taken from.
+struct MeshJob {
+    version: u64,
+    chunk_pos: ChunkPos,
+    snapshot: ChunkSnapshot,
+    mesher: MesherKind,
+}
+
+// Mesh data on its way back to the main thread.
+pub struct MeshedChunk {
+    version: u64,
+    pub chunk_pos: ChunkPos,
+    pub data: ChunkMeshData,
+}
+
+// Pool of threads meshing chunk snapshots off the main thread.
+//
+// A chunk edited while its mesh is being built is submitted again with a newer version; only the
+// result of the newest job is handed back, so a stale mesh never replaces a fresh one.
+pub struct MeshWorkers {
+    jobs: Sender<MeshJob>,
+    results: Receiver<MeshedChunk>,
+    // version of the newest job of each chunk still being meshed
+    latest: HashMap<ChunkPos, u64>,
+    next_version: u64,
+}
+
+impl MeshWorkers {
+    pub fn new() -> Self {
+        let (jobs, results) = spawn_workers("mesh", |job: MeshJob| {
+            let data = mesh_builder::build_mesh_data(&job.snapshot, &job.chunk_pos, job.mesher);
+            MeshedChunk { version: job.version, chunk_pos: job.chunk_pos, data }
+        });
+
+        Self {
+            jobs,
+            results,
+            latest: HashMap::new(),
+            next_version: 0,
+        }
+    }
+
+    // queues a snapshot for meshing, superseding any job of the same chunk still running
+    pub fn submit(&mut self, chunk_pos: ChunkPos, snapshot: ChunkSnapshot, mesher: MesherKind) {
+        let job = MeshJob { version: self.next_version, chunk_pos, snapshot, mesher };
+        self.latest.insert(chunk_pos, self.next_version);
+        self.next_version += 1;
+        self.jobs.send(job).expect("mesh workers stopped");
+    }
+
+    pub fn in_flight(&self) -> usize {
+        self.latest.len()
+    }
+
+    // meshes finished since the last call, without the ones that were superseded or cancelled
+    pub fn collect(&mut self) -> Vec<MeshedChunk> {
+        let mut ready = Vec::new();
+        while let Ok(result) = self.results.try_recv() {
+            if self.latest.get(&result.chunk_pos) == Some(&result.version) {
+                self.latest.remove(&result.chunk_pos);
+                ready.push(result);
+            }
+        }
+        ready
+    }
+
+    pub fn cancel(&mut self, chunk_pos: &ChunkPos) {
+        self.latest.remove(chunk_pos);
+    }
+
+    pub fn cancel_all(&mut self) {
+        self.latest.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use cgmath::Vector3;
+
+    use super::*;
+    use crate::voxgl::{config::Config, world::terrain_config::TerrainConfig};
+
+    // workers whose jobs and results go through the test instead of worker threads
+    fn manual_workers() -> (GenerationWorkers, Receiver<GenerationJob>, Sender<GeneratedChunk>) {
+        let (jobs, job_receiver) = mpsc::channel();
+        let (result_sender, results) = mpsc::channel();
+        let workers = GenerationWorkers {
+            jobs,
+            results,
+            in_flight: HashSet::new(),
+            finished: BTreeMap::new(),
+            next_ticket: 0,
+            next_result: 0,
+            epoch: 0,
+            discarded: Vec::new(),
+        };
+        (workers, job_receiver, result_sender)
+    }
+
+    fn finish(job: GenerationJob) -> GeneratedChunk {
+        let GenerationJob { ticket, epoch, chunk_pos, chunk, .. } = job;
+        GeneratedChunk { ticket, epoch, chunk_pos, chunk, structures: Vec::new() }
+    }
+
+    fn submit(
+        workers: &mut GenerationWorkers, jobs: &Receiver<GenerationJob>, world: &Arc<WorldGenerator>, x: i32
+    ) -> GenerationJob {
+        workers.submit(ChunkPos(Vector3::new(x, 0, 0)), ChunkData::new(), world);
+        jobs.try_recv().unwrap()
+    }
+
+    fn positions(results: &[GeneratedChunk]) -> Vec<i32> {
+        results.iter().map(|result| result.chunk_pos.x).collect()
+    }
+
+    #[test]
+    fn results_come_back_in_ticket_order_without_stale_epochs() {
+        let world = Arc::new(WorldGenerator::new(&Config::default(), &TerrainConfig::default()).unwrap());
+        let (mut workers, jobs, results) = manual_workers();
+
+        let [a0, a1, a2] = [0, 1, 2].map(|x| submit(&mut workers, &jobs, &world, x));
+        results.send(finish(a2)).unwrap();
+        results.send(finish(a0)).unwrap();
+        // the third chunk waits for the second
+        assert_eq!(positions(&workers.collect()), [0]);
+        assert!(workers.is_generating(&ChunkPos(Vector3::new(2, 0, 0))));
+
+        workers.cancel_all();
+        assert_eq!(workers.in_flight(), 0);
+
+        let [b0, b1, b2] = [10, 11, 12].map(|x| submit(&mut workers, &jobs, &world, x));
+        // a job of the cancelled epoch finishing late shares its ticket with a new one
+        results.send(finish(a1)).unwrap();
+        results.send(finish(b2)).unwrap();
+        results.send(finish(b1)).unwrap();
+        assert!(workers.collect().is_empty());
+
+        results.send(finish(b0)).unwrap();
+        assert_eq!(positions(&workers.collect()), [10, 11, 12]);
+        assert_eq!(workers.in_flight(), 0);
+
+        // the held back and the late result of the old epoch are handed back for reuse
+        assert_eq!(workers.take_discarded().len(), 2);
+        assert!(workers.take_discarded().is_empty());
+    }
+}